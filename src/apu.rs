@@ -0,0 +1,731 @@
+pub const APU_STATUS: u16 = 0x4015;
+pub const APU_FRAME_COUNTER: u16 = 0x4017;
+
+// Frame sequencer steps, in CPU cycles since the sequencer was last reset
+const FRAME_STEP_1: u16 = 7457;
+const FRAME_STEP_2: u16 = 14913;
+const FRAME_STEP_3: u16 = 22371;
+const FRAME_STEP_4: u16 = 29829;
+const FRAME_STEP_5: u16 = 37281;
+
+const LENGTH_TABLE: [u8; 32] = [10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30];
+
+const DUTY_TABLE: [[u8; 8]; 4] = [[0, 1, 0, 0, 0, 0, 0, 0], [0, 1, 1, 0, 0, 0, 0, 0], [0, 1, 1, 1, 1, 0, 0, 0], [1, 0, 0, 1, 1, 1, 1, 1]];
+
+const TRIANGLE_SEQUENCE: [u8; 32] = [15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+// In CPU cycles
+const NOISE_PERIOD_TABLE: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+
+// In CPU cycles
+const DMC_RATE_TABLE: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+
+#[derive(Default)]
+struct Envelope {
+    start: bool,
+    looping: bool,
+    constant_volume: bool,
+    // Either the constant volume or the envelope's divider period
+    volume: u8,
+    divider: u8,
+    decay_level: u8,
+}
+
+impl Envelope {
+    fn write(&mut self, val: u8) {
+        self.looping = (val & 0b00100000) != 0;
+        self.constant_volume = (val & 0b00010000) != 0;
+        self.volume = val & 0b1111;
+    }
+
+    fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay_level = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay_level > 0 {
+                self.decay_level -= 1;
+            } else if self.looping {
+                self.decay_level = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay_level
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pulse {
+    // Pulse 1 negates with ones' complement, pulse 2 with two's complement
+    is_pulse_1: bool,
+    enabled: bool,
+
+    duty: u8,
+    sequence_step: usize,
+    timer_period: u16,
+    timer: u16,
+    length_counter: u8,
+    // Doubles as the envelope loop flag
+    length_counter_halt: bool,
+    envelope: Envelope,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl Pulse {
+    fn write_register(&mut self, i: u16, val: u8) {
+        match i {
+            0 => {
+                self.duty = val >> 6;
+                self.length_counter_halt = (val & 0b00100000) != 0;
+                self.envelope.write(val);
+            }
+            1 => {
+                self.sweep_enabled = (val & 0b10000000) != 0;
+                self.sweep_period = (val >> 4) & 0b111;
+                self.sweep_negate = (val & 0b00001000) != 0;
+                self.sweep_shift = val & 0b111;
+                self.sweep_reload = true;
+            }
+            2 => self.timer_period = (self.timer_period & 0xff00) | val as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00ff) | (((val & 0b111) as u16) << 8);
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[(val >> 3) as usize];
+                }
+                self.sequence_step = 0;
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    // Clocked every APU cycle, i.e. every other CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = (self.sequence_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn sweep_target_period(&self) -> u16 {
+        let change: u16 = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            self.timer_period.saturating_sub(change + self.is_pulse_1 as u16)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    // The sweep unit silences the channel when the period is too low, or when it would sweep out of range,
+    // even if sweeping is disabled
+    fn is_muted_by_sweep(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target_period() > 0x7ff
+    }
+
+    fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.is_muted_by_sweep() {
+            self.timer_period = self.sweep_target_period();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn clock_length_counter(&mut self) {
+        if !self.length_counter_halt && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length_counter == 0 || self.is_muted_by_sweep() || DUTY_TABLE[self.duty as usize][self.sequence_step] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[derive(Default)]
+struct Triangle {
+    enabled: bool,
+
+    sequence_step: usize,
+    timer_period: u16,
+    timer: u16,
+    length_counter: u8,
+    // Doubles as the length counter halt flag
+    linear_counter_control: bool,
+    linear_counter_reload_value: u8,
+    linear_counter: u8,
+    linear_counter_reload: bool,
+}
+
+impl Triangle {
+    fn write_register(&mut self, i: u16, val: u8) {
+        match i {
+            0 => {
+                self.linear_counter_control = (val & 0b10000000) != 0;
+                self.linear_counter_reload_value = val & 0b01111111;
+            }
+            1 => {}
+            2 => self.timer_period = (self.timer_period & 0xff00) | val as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00ff) | (((val & 0b111) as u16) << 8);
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[(val >> 3) as usize];
+                }
+                self.linear_counter_reload = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            // Periods below 2 produce ultrasonic frequencies that only add popping, so the sequencer is held instead
+            if self.length_counter > 0 && self.linear_counter > 0 && self.timer_period >= 2 {
+                self.sequence_step = (self.sequence_step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_linear_counter(&mut self) {
+        if self.linear_counter_reload {
+            self.linear_counter = self.linear_counter_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.linear_counter_control {
+            self.linear_counter_reload = false;
+        }
+    }
+
+    fn clock_length_counter(&mut self) {
+        if !self.linear_counter_control && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        TRIANGLE_SEQUENCE[self.sequence_step]
+    }
+}
+
+struct Noise {
+    enabled: bool,
+
+    // In mode 1 the feedback comes from bit 6 rather than bit 1, giving a short, metallic sounding sequence
+    mode: bool,
+    shift_register: u16,
+    timer_period: u16,
+    timer: u16,
+    length_counter: u8,
+    // Doubles as the envelope loop flag
+    length_counter_halt: bool,
+    envelope: Envelope,
+}
+
+impl Default for Noise {
+    fn default() -> Noise {
+        Noise {
+            enabled: false,
+            mode: false,
+            shift_register: 1,
+            timer_period: NOISE_PERIOD_TABLE[0],
+            timer: 0,
+            length_counter: 0,
+            length_counter_halt: false,
+            envelope: Default::default(),
+        }
+    }
+}
+
+impl Noise {
+    fn write_register(&mut self, i: u16, val: u8) {
+        match i {
+            0 => {
+                self.length_counter_halt = (val & 0b00100000) != 0;
+                self.envelope.write(val);
+            }
+            1 => {}
+            2 => {
+                self.mode = (val & 0b10000000) != 0;
+                self.timer_period = NOISE_PERIOD_TABLE[(val & 0b1111) as usize];
+            }
+            _ => {
+                if self.enabled {
+                    self.length_counter = LENGTH_TABLE[(val >> 3) as usize];
+                }
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let feedback_bit: u16 = if self.mode { 6 } else { 1 };
+            let feedback: u16 = (self.shift_register ^ (self.shift_register >> feedback_bit)) & 1;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_length_counter(&mut self) {
+        if !self.length_counter_halt && self.length_counter > 0 {
+            self.length_counter -= 1;
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter = 0;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length_counter == 0 || (self.shift_register & 1) != 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+struct Dmc {
+    irq_enabled: bool,
+    irq_flag: bool,
+    looping: bool,
+    timer_period: u16,
+    timer: u16,
+
+    sample_addr: u16,
+    sample_length: u16,
+    current_addr: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    output_level: u8,
+}
+
+impl Default for Dmc {
+    fn default() -> Dmc {
+        Dmc {
+            irq_enabled: false,
+            irq_flag: false,
+            looping: false,
+            timer_period: DMC_RATE_TABLE[0],
+            timer: 0,
+            sample_addr: 0xc000,
+            sample_length: 1,
+            current_addr: 0xc000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            output_level: 0,
+        }
+    }
+}
+
+impl Dmc {
+    fn write_register(&mut self, i: u16, val: u8) {
+        match i {
+            0 => {
+                self.irq_enabled = (val & 0b10000000) != 0;
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+                self.looping = (val & 0b01000000) != 0;
+                self.timer_period = DMC_RATE_TABLE[(val & 0b1111) as usize];
+            }
+            1 => self.output_level = val & 0b01111111,
+            2 => self.sample_addr = 0xc000 + val as u16 * 64,
+            _ => self.sample_length = val as u16 * 16 + 1,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_addr = self.sample_addr;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // Clocked every CPU cycle
+    fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+
+        if !self.silence {
+            if (self.shift_register & 1) != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    // The address of the next sample byte, if the sample buffer needs refilling
+    fn sample_request(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_addr)
+        } else {
+            None
+        }
+    }
+
+    fn load_sample(&mut self, sample: u8) {
+        self.sample_buffer = Some(sample);
+        self.current_addr = if self.current_addr == 0xffff { 0x8000 } else { self.current_addr + 1 };
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn output(&self) -> u8 {
+        self.output_level
+    }
+}
+
+#[derive(Default)]
+pub struct Apu {
+    pulse_1: Pulse,
+    pulse_2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    // Pulse timers only tick on every other CPU cycle
+    odd_cycle: bool,
+
+    frame_counter: u16,
+    five_step_mode: bool,
+    frame_irq_inhibit: bool,
+    frame_irq_flag: bool,
+    // Writes to $4017 only reset the sequencer after a few cycles
+    frame_counter_reset_delay: Option<u8>,
+}
+
+impl Apu {
+    pub fn new() -> Apu {
+        let mut result: Apu = Default::default();
+        result.pulse_1.is_pulse_1 = true;
+        result
+    }
+
+    pub fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x4000..=0x4003 => self.pulse_1.write_register(addr % 4, val),
+            0x4004..=0x4007 => self.pulse_2.write_register(addr % 4, val),
+            0x4008..=0x400b => self.triangle.write_register(addr % 4, val),
+            0x400c..=0x400f => self.noise.write_register(addr % 4, val),
+            0x4010..=0x4013 => self.dmc.write_register(addr % 4, val),
+            APU_FRAME_COUNTER => {
+                self.five_step_mode = (val & 0b10000000) != 0;
+                self.frame_irq_inhibit = (val & 0b01000000) != 0;
+                if self.frame_irq_inhibit {
+                    self.frame_irq_flag = false;
+                }
+                self.frame_counter_reset_delay = Some(if self.odd_cycle { 4 } else { 3 });
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+            APU_STATUS => {
+                self.pulse_1.set_enabled((val & 0b00001) != 0);
+                self.pulse_2.set_enabled((val & 0b00010) != 0);
+                self.triangle.set_enabled((val & 0b00100) != 0);
+                self.noise.set_enabled((val & 0b01000) != 0);
+                self.dmc.set_enabled((val & 0b10000) != 0);
+                self.dmc.irq_flag = false;
+            }
+            _ => {}
+        }
+    }
+
+    // Resetting silences every channel and restarts the frame counter as though its last value had been written again
+    pub fn reset(&mut self) {
+        self.write_register(APU_STATUS, 0);
+        self.write_register(APU_FRAME_COUNTER, ((self.five_step_mode as u8) << 7) | ((self.frame_irq_inhibit as u8) << 6));
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        let frame_irq_flag = self.frame_irq_flag;
+        // Reading the status register acknowledges the frame interrupt
+        self.frame_irq_flag = false;
+        ((self.dmc.irq_flag as u8) << 7)
+            | ((frame_irq_flag as u8) << 6)
+            | (((self.dmc.bytes_remaining > 0) as u8) << 4)
+            | (((self.noise.length_counter > 0) as u8) << 3)
+            | (((self.triangle.length_counter > 0) as u8) << 2)
+            | (((self.pulse_2.length_counter > 0) as u8) << 1)
+            | ((self.pulse_1.length_counter > 0) as u8)
+    }
+
+    // Envelopes and the triangle's linear counter
+    fn clock_quarter_frame(&mut self) {
+        self.pulse_1.envelope.clock();
+        self.pulse_2.envelope.clock();
+        self.triangle.clock_linear_counter();
+        self.noise.envelope.clock();
+    }
+
+    // Length counters and sweep units
+    fn clock_half_frame(&mut self) {
+        self.pulse_1.clock_length_counter();
+        self.pulse_1.clock_sweep();
+        self.pulse_2.clock_length_counter();
+        self.pulse_2.clock_sweep();
+        self.triangle.clock_length_counter();
+        self.noise.clock_length_counter();
+    }
+
+    fn clock_frame_counter(&mut self) {
+        if let Some(delay) = self.frame_counter_reset_delay {
+            if delay == 0 {
+                self.frame_counter_reset_delay = None;
+                self.frame_counter = 0;
+            } else {
+                self.frame_counter_reset_delay = Some(delay - 1);
+            }
+        }
+
+        self.frame_counter += 1;
+        match self.frame_counter {
+            FRAME_STEP_1 | FRAME_STEP_3 => self.clock_quarter_frame(),
+            FRAME_STEP_2 => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            _ if self.five_step_mode => {
+                if self.frame_counter == FRAME_STEP_5 {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                } else if self.frame_counter == FRAME_STEP_5 + 1 {
+                    self.frame_counter = 0;
+                }
+            }
+            // The frame interrupt flag is set on the three cycles around the last step
+            _ => {
+                if self.frame_counter == FRAME_STEP_4 {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+                if (FRAME_STEP_4 - 1..=FRAME_STEP_4 + 1).contains(&self.frame_counter) && !self.frame_irq_inhibit {
+                    self.frame_irq_flag = true;
+                }
+                if self.frame_counter == FRAME_STEP_4 + 1 {
+                    self.frame_counter = 0;
+                }
+            }
+        }
+    }
+
+    // The APU's two sources of IRQs
+    pub fn frame_irq(&self) -> bool {
+        self.frame_irq_flag
+    }
+
+    pub fn dmc_irq(&self) -> bool {
+        self.dmc.irq_flag
+    }
+
+    // Advances the APU by one CPU cycle
+    pub fn tick(&mut self) {
+        self.clock_frame_counter();
+        if self.odd_cycle {
+            self.pulse_1.clock_timer();
+            self.pulse_2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+    }
+
+    // The DMC reads its samples from CPU memory, so the CPU has to service these requests
+    pub fn dmc_sample_request(&self) -> Option<u16> {
+        self.dmc.sample_request()
+    }
+
+    pub fn dmc_load_sample(&mut self, sample: u8) {
+        self.dmc.load_sample(sample);
+    }
+
+    // The current output level of each channel: pulse 1, pulse 2, triangle, noise and DMC
+    pub fn channel_outputs(&self) -> [u8; CHANNEL_COUNT] {
+        [self.pulse_1.output(), self.pulse_2.output(), self.triangle.output(), self.noise.output(), self.dmc.output()]
+    }
+}
+
+/// How many channels the APU has
+pub const CHANNEL_COUNT: usize = 5;
+/// Each channel's name, in the order the mixer indexes them
+pub const CHANNEL_NAMES: [&str; CHANNEL_COUNT] = ["pulse 1", "pulse 2", "triangle", "noise", "DMC"];
+/// The loudest a channel can be turned up to, as a multiple of its normal volume
+pub const MAX_CHANNEL_VOLUME: f32 = 2.0;
+
+/// Combines the channels into one output, with a volume and mute switch for each channel.
+/// Channels are indexed in the order of CHANNEL_NAMES. Setting a channel that doesn't exist is ignored, and it reads as muted
+/// at zero volume.
+pub struct Mixer {
+    volumes: [f32; CHANNEL_COUNT],
+    muted: [bool; CHANNEL_COUNT],
+    // When set, only this channel is heard
+    solo: Option<usize>,
+}
+
+impl Default for Mixer {
+    fn default() -> Mixer {
+        Mixer {
+            volumes: [1.0; CHANNEL_COUNT],
+            muted: [false; CHANNEL_COUNT],
+            solo: None,
+        }
+    }
+}
+
+impl Mixer {
+    /// As a multiple of the channel's normal volume
+    pub fn volume(&self, channel: usize) -> f32 {
+        self.volumes.get(channel).copied().unwrap_or(0.0)
+    }
+
+    /// Clamped to between 0.0 and MAX_CHANNEL_VOLUME
+    pub fn set_volume(&mut self, channel: usize, volume: f32) {
+        if let Some(channel_volume) = self.volumes.get_mut(channel) {
+            *channel_volume = volume.clamp(0.0, MAX_CHANNEL_VOLUME);
+        }
+    }
+
+    pub fn is_muted(&self, channel: usize) -> bool {
+        self.muted.get(channel).copied().unwrap_or(true)
+    }
+
+    pub fn set_muted(&mut self, channel: usize, muted: bool) {
+        if let Some(channel_muted) = self.muted.get_mut(channel) {
+            *channel_muted = muted;
+        }
+    }
+
+    /// The only channel being heard, if any, regardless of which are muted
+    pub fn solo(&self) -> Option<usize> {
+        self.solo
+    }
+
+    pub fn set_solo(&mut self, solo: Option<usize>) {
+        if solo.is_none_or(|channel: usize| channel < CHANNEL_COUNT) {
+            self.solo = solo;
+        }
+    }
+
+    /// Whether the channel currently contributes to the output
+    pub fn is_audible(&self, channel: usize) -> bool {
+        match self.solo {
+            Some(solo) => solo == channel,
+            None => !self.is_muted(channel),
+        }
+    }
+
+    pub(crate) fn mix(&self, outputs: [u8; CHANNEL_COUNT]) -> f32 {
+        let mut levels: [f32; CHANNEL_COUNT] = [0.0; CHANNEL_COUNT];
+        for (channel, level) in levels.iter_mut().enumerate() {
+            if self.is_audible(channel) {
+                *level = outputs[channel] as f32 * self.volumes[channel];
+            }
+        }
+        mix(levels)
+    }
+}
+
+// What a single channel sounds like on its own at normal volume
+pub fn mix_channel(channel: usize, outputs: [u8; CHANNEL_COUNT]) -> f32 {
+    let mut levels: [f32; CHANNEL_COUNT] = [0.0; CHANNEL_COUNT];
+    levels[channel] = outputs[channel] as f32;
+    mix(levels)
+}
+
+// Mixes the channels the way the 2A03's nonlinear DACs do, giving a level between 0.0 and 1.0 at normal volume
+fn mix([pulse_1, pulse_2, triangle, noise, dmc]: [f32; CHANNEL_COUNT]) -> f32 {
+    let pulse: f32 = pulse_1 + pulse_2;
+    let pulse_out: f32 = if pulse == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulse + 100.0) };
+    let tnd: f32 = triangle / 8227.0 + noise / 12241.0 + dmc / 22638.0;
+    let tnd_out: f32 = if tnd == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd + 100.0) };
+    pulse_out + tnd_out
+}
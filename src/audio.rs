@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// The NTSC CPU clock, which is also the rate at which the APU produces samples
+pub const CPU_CLOCK_RATE: f64 = 21477272.0 / 12.0;
+pub const DEFAULT_SAMPLE_RATE: f64 = 48000.0;
+// How far dynamic rate control may stretch or squeeze the output rate
+const MAX_RATE_DELTA: f64 = 0.005;
+
+// The NES's output stage: two high-pass filters and a low-pass filter, in Hz
+const HIGH_PASS_1_CUTOFF: f64 = 90.0;
+const HIGH_PASS_2_CUTOFF: f64 = 440.0;
+const LOW_PASS_CUTOFF: f64 = 14000.0;
+
+// A first-order RC filter
+struct Filter {
+    high_pass: bool,
+    alpha: f32,
+    prev_input: f32,
+    prev_output: f32,
+}
+
+impl Filter {
+    fn new(high_pass: bool, cutoff: f64, sample_rate: f64) -> Filter {
+        let rc: f64 = 1.0 / (2.0 * PI * cutoff);
+        let dt: f64 = 1.0 / sample_rate;
+        Filter {
+            high_pass,
+            alpha: (if high_pass { rc / (rc + dt) } else { dt / (rc + dt) }) as f32,
+            prev_input: 0.0,
+            prev_output: 0.0,
+        }
+    }
+
+    fn apply(&mut self, input: f32) -> f32 {
+        let output: f32 = if self.high_pass { self.alpha * (self.prev_output + input - self.prev_input) } else { self.prev_output + self.alpha * (input - self.prev_output) };
+        self.prev_input = input;
+        self.prev_output = output;
+        output
+    }
+}
+
+// Band-limited steps are spread over this many output samples
+const KERNEL_TAPS: usize = 32;
+// How finely the position of a step between two output samples is resolved
+const KERNEL_PHASES: usize = 512;
+// The anti-aliasing filter's cutoff, as a fraction of the output rate. It's kept a little under the Nyquist frequency so that
+// the filter's transition band, rather than the aliases, is what ends up in the top of the spectrum.
+const KERNEL_CUTOFF: f64 = 0.45;
+
+// The band-limited impulse for a step landing `phase / KERNEL_PHASES` of the way between two output samples: a
+// Blackman-windowed sinc, normalized so that summing it gives exactly the height of the step
+fn kernel(phase: usize) -> [f32; KERNEL_TAPS] {
+    let center: f64 = (KERNEL_TAPS / 2) as f64 + phase as f64 / KERNEL_PHASES as f64;
+    let mut taps: [f64; KERNEL_TAPS] = [0.0; KERNEL_TAPS];
+    for (k, tap) in taps.iter_mut().enumerate() {
+        let x: f64 = k as f64 - center;
+        let sinc: f64 = if x == 0.0 { 1.0 } else { (2.0 * PI * KERNEL_CUTOFF * x).sin() / (PI * x) / (2.0 * KERNEL_CUTOFF) };
+        let window: f64 = if x.abs() >= (KERNEL_TAPS / 2) as f64 {
+            0.0
+        } else {
+            0.42 + 0.5 * (2.0 * PI * x / KERNEL_TAPS as f64).cos() + 0.08 * (4.0 * PI * x / KERNEL_TAPS as f64).cos()
+        };
+        *tap = sinc * window;
+    }
+    let sum: f64 = taps.iter().sum();
+    taps.map(|tap: f64| (tap / sum) as f32)
+}
+
+// Converts the APU's output at the CPU clock rate down to the audio device's rate.
+// The APU's output only ever changes in steps, so each step is drawn band-limited, spread out over the neighboring output
+// samples by a windowed sinc. That keeps everything above the output's Nyquist frequency from aliasing back down into it.
+pub struct Resampler {
+    sample_rate: f64,
+    // Input samples per output sample, nudged by dynamic rate control
+    input_per_output: f64,
+    // Where the next input sample falls, in output samples after the oldest one still being built
+    time: f64,
+    prev_input: f32,
+    // The band-limited steps landing on each output sample still being built. The output is their running sum.
+    deltas: VecDeque<f32>,
+    level: f32,
+    kernels: Vec<[f32; KERNEL_TAPS]>,
+    filters: [Filter; 3],
+    samples: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(sample_rate: f64) -> Resampler {
+        Resampler {
+            sample_rate,
+            input_per_output: CPU_CLOCK_RATE / sample_rate,
+            time: 0.0,
+            prev_input: 0.0,
+            deltas: VecDeque::from(vec![0.0; KERNEL_TAPS + 1]),
+            level: 0.0,
+            kernels: (0..=KERNEL_PHASES).map(kernel).collect(),
+            filters: [Filter::new(true, HIGH_PASS_1_CUTOFF, sample_rate), Filter::new(true, HIGH_PASS_2_CUTOFF, sample_rate), Filter::new(false, LOW_PASS_CUTOFF, sample_rate)],
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, input: f32) {
+        let delta: f32 = input - self.prev_input;
+        if delta != 0.0 {
+            self.prev_input = input;
+            let phase: usize = (self.time.fract() * KERNEL_PHASES as f64).round() as usize;
+            for (k, tap) in self.kernels[phase].iter().enumerate() {
+                self.deltas[self.time as usize + k] += delta * tap;
+            }
+        }
+        self.time += 1.0 / self.input_per_output;
+
+        // Once the input has moved past an output sample, no later step can land on it
+        while self.time >= 1.0 {
+            self.time -= 1.0;
+            self.level += self.deltas.pop_front().unwrap_or(0.0);
+            self.deltas.push_back(0.0);
+            let mut sample: f32 = self.level;
+            for filter in self.filters.iter_mut() {
+                sample = filter.apply(sample);
+            }
+            self.samples.push(sample);
+        }
+    }
+
+    // Speeds up or slows down the output by a fraction of a percent to keep the device's queue near `target` samples.
+    // The pitch change is inaudible, but it stops the queue from running dry or piling up when frame pacing drifts.
+    pub fn adjust_rate(&mut self, queued: usize, target: usize) {
+        let error: f64 = ((queued as f64 - target as f64) / target as f64).clamp(-1.0, 1.0);
+        self.input_per_output = CPU_CLOCK_RATE / self.sample_rate * (1.0 + error * MAX_RATE_DELTA);
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+}
+
+impl Default for Resampler {
+    fn default() -> Resampler {
+        Resampler::new(DEFAULT_SAMPLE_RATE)
+    }
+}
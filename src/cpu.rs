@@ -0,0 +1,713 @@
+use crate::opcodes::{self, AddressingMode, Class, Mnemonic, Opcode, OPCODES};
+
+const RESET_VECTOR: u16 = 0xfffc;
+const BRK_VECTOR: u16 = 0xfffe;
+const IRQ_VECTOR: u16 = 0xfffe;
+const NMI_VECTOR: u16 = 0xfffa;
+// XAA and LXA mix in a value that varies between chips and with temperature. This is the commonly accepted one.
+const UNSTABLE_MAGIC: u8 = 0xee;
+
+/// Everything the CPU is wired to: memory, memory-mapped devices and the interrupt lines
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+    /// Runs the rest of the system for one CPU cycle, just before the CPU's bus access on it
+    fn tick(&mut self);
+    /// Reads memory without the side effects of reading registers, for the tracer
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Whether the NMI line has been asserted since the last call. NMI is edge-triggered, so each edge is only reported once.
+    fn nmi(&mut self) -> bool {
+        false
+    }
+
+    /// Whether anything is holding the IRQ line
+    fn irq(&mut self) -> bool {
+        false
+    }
+
+    /// Lets devices like DMA units take the bus over before the CPU's next read cycle, which is the only kind of cycle the CPU can be halted on.
+    /// Returns how many cycles the CPU was halted for.
+    fn dma(&mut self) -> u64 {
+        0
+    }
+}
+
+/// A 6502 that runs its instructions a bus access, and so a cycle, at a time
+pub struct Cpu6502<B: Bus> {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    pub pc: u16,
+    pub carry: bool,
+    pub zero: bool,
+    pub interrupt_disable: bool,
+    pub decimal_mode: bool,
+    pub overflow: bool,
+    pub negative: bool,
+
+    pub cycles: u64,
+    // Whether an NMI or IRQ should be taken, as of the last cycle and the one before it
+    need_nmi: bool,
+    prev_need_nmi: bool,
+    run_irq: bool,
+    prev_run_irq: bool,
+    // How many cycles the CPU has spent halted by DMA
+    dma_cycles: u64,
+    /// Whether to print every instruction as it runs
+    pub trace: bool,
+
+    pub bus: B,
+}
+
+impl<B: Bus> Cpu6502<B> {
+    /// Starts running from the address in the bus's reset vector
+    pub fn new(bus: B) -> Self {
+        let mut result: Self = Cpu6502 {
+            a: 0,
+            x: 0,
+            y: 0,
+            s: 0xfd,
+            pc: 0x0000,
+            carry: false,
+            zero: false,
+            interrupt_disable: true,
+            decimal_mode: false,
+            overflow: false,
+            negative: false,
+            cycles: 0,
+            need_nmi: false,
+            prev_need_nmi: false,
+            run_irq: false,
+            prev_run_irq: false,
+            dma_cycles: 0,
+            trace: false,
+            bus,
+        };
+        result.pc = ((result.bus.read(RESET_VECTOR.wrapping_add(1)) as u16) << 8) | (result.bus.read(RESET_VECTOR) as u16);
+        result
+    }
+
+    /// Reset runs the interrupt sequence with its pushes turned into reads, so S still goes down by three
+    pub fn reset(&mut self) {
+        self.idle();
+        self.idle();
+        for _ in 0..3 {
+            self.idle_stack();
+            self.s = self.s.wrapping_sub(1);
+        }
+        self.interrupt_disable = true;
+        let low: u8 = self.read(RESET_VECTOR);
+        let high: u8 = self.read(RESET_VECTOR.wrapping_add(1));
+        self.pc = ((high as u16) << 8) | (low as u16);
+        self.need_nmi = false;
+        self.prev_need_nmi = false;
+        self.run_irq = false;
+        self.prev_run_irq = false;
+    }
+
+    fn dump_regs(&self) {
+        println!("A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}", self.a, self.x, self.y, self.get_flags_byte(false), self.s, self.cycles);
+    }
+
+    fn update_nz_flags(&mut self, val: u8) {
+        self.zero = val == 0;
+        self.negative = (val >> 7) != 0;
+    }
+
+    /// The status register as pushed to the stack, with the B flag set as given
+    pub fn get_flags_byte(&self, b: bool) -> u8 {
+        ((self.negative as u8) << 7) | ((self.overflow as u8) << 6) | (1u8 << 5) | ((b as u8) << 4) | ((self.decimal_mode as u8) << 3) | ((self.interrupt_disable as u8) << 2) | ((self.zero as u8) << 1) | (self.carry as u8)
+    }
+
+    fn push(&mut self, val: u8) {
+        self.write((self.s as u16).wrapping_add(0x100), val);
+        self.s = self.s.wrapping_sub(1);
+    }
+
+    fn push16(&mut self, val: u16) {
+        self.push((val >> 8) as u8);
+        self.push(val as u8);
+    }
+
+    fn pop(&mut self) -> u8 {
+        self.s = self.s.wrapping_add(1);
+        self.read((self.s as u16).wrapping_add(0x100))
+    }
+
+    fn pop16(&mut self) -> u16 {
+        let low_bits: u8 = self.pop();
+        let high_bits: u8 = self.pop();
+        ((high_bits as u16) << 8) | (low_bits as u16)
+    }
+
+    fn pop_flags(&mut self) {
+        let result: u8 = self.pop();
+        self.negative = (result & 0b10000000) != 0;
+        self.overflow = (result & 0b01000000) != 0;
+        self.interrupt_disable = (result & 0b00000100) != 0; // Changes after the last cycle's interrupt poll, so PLP's effect is delayed by an instruction
+        self.decimal_mode = (result & 0b00001000) != 0;
+        self.carry = (result & 0b00000001) != 0;
+        self.zero = (result & 0b00000010) != 0;
+    }
+
+    fn adc(&mut self, op: u8) -> u8 {
+        let result_16: u16 = (self.a as u16).wrapping_add(op as u16).wrapping_add(self.carry as u16);
+        let result = result_16 as u8;
+
+        self.carry = result_16 > 255;
+        self.overflow = (is_negative(self.a) == is_negative(op)) && (is_negative(result) != is_negative(op));
+        self.update_nz_flags(result);
+
+        result
+    }
+
+    fn and(&mut self, op: u8) -> u8 {
+        let result: u8 = self.a & op;
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn asl(&mut self, op: u8) -> u8 {
+        let result: u8 = op << 1;
+        self.update_nz_flags(result);
+        self.carry = is_negative(op);
+        result
+    }
+
+    fn bit(&mut self, op: u8) {
+        let result: u8 = self.a & op;
+
+        self.zero = result == 0;
+        self.overflow = (op & 0b01000000) != 0;
+        self.negative = is_negative(op);
+    }
+
+    fn cmp(&mut self, op1: u8, op2: u8) {
+        self.carry = op1 >= op2;
+        self.update_nz_flags(op1.wrapping_sub(op2));
+    }
+
+    fn dec(&mut self, val: u8) -> u8 {
+        let result: u8 = val.wrapping_sub(1);
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn eor(&mut self, op: u8) -> u8 {
+        let result: u8 = self.a ^ op;
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn inc(&mut self, val: u8) -> u8 {
+        let result: u8 = val.wrapping_add(1);
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn lsr(&mut self, op: u8) -> u8 {
+        let result: u8 = op >> 1;
+        self.update_nz_flags(result);
+        self.carry = (op & 1) != 0;
+        result
+    }
+
+    fn ora(&mut self, op: u8) -> u8 {
+        let result: u8 = self.a | op;
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn rol(&mut self, op: u8) -> u8 {
+        let result: u8 = (op << 1) | (self.carry as u8);
+        self.carry = is_negative(op);
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn ror(&mut self, op: u8) -> u8 {
+        let result: u8 = ((self.carry as u8) << 7) | (op >> 1);
+        self.carry = (op & 1) != 0;
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn sbc(&mut self, op: u8) -> u8 {
+        let result_16: i16 = (self.a as i16) - (op as i16) - (!self.carry as i16);
+        let result: u8 = result_16 as u8;
+        self.carry = result_16 >= 0;
+        self.overflow = (is_negative(result) != is_negative(self.a)) && (is_negative(result) == is_negative(op));
+        self.update_nz_flags(result);
+        result
+    }
+
+    fn branch(&mut self, cond: bool) {
+        let offset: u8 = self.fetch();
+        if !cond {
+            return;
+        }
+
+        // A taken branch that stays on the same page doesn't poll for IRQs on its last cycle, so one that arrives during it waits an instruction
+        if self.run_irq && !self.prev_run_irq {
+            self.run_irq = false;
+        }
+        self.idle();
+        let new_pc: u16 = self.pc.wrapping_add(offset as i8 as u16);
+        if new_pc & 0xff00 != self.pc & 0xff00 {
+            self.read((self.pc & 0xff00) | (new_pc & 0x00ff));
+        }
+        self.pc = new_pc;
+    }
+
+    // SHA, SHX, SHY and TAS store a value ANDed with the high byte of the base address plus one.
+    // When indexing crosses a page, the stored value also replaces the high byte of the address.
+    fn unstable_store(&mut self, mode: AddressingMode, val: u8) {
+        let (base, index): (u16, u8) = match mode {
+            AddressingMode::AbsoluteX => (self.fetch16(), self.x),
+            AddressingMode::AbsoluteY => (self.fetch16(), self.y),
+            _ => {
+                let ptr: u8 = self.fetch();
+                (self.read_zero_page_pointer(ptr), self.y)
+            }
+        };
+        let addr: u16 = base.wrapping_add(index as u16);
+        self.read((base & 0xff00) | (addr & 0x00ff));
+        let result: u8 = val & ((base >> 8) as u8).wrapping_add(1);
+        let addr: u16 = if addr & 0xff00 != base & 0xff00 { ((result as u16) << 8) | (addr & 0x00ff) } else { addr };
+        self.write(addr, result);
+    }
+
+    // The sequence shared by BRK, IRQ and NMI. The vector is only chosen after the pushes,
+    // so an NMI that arrives by then hijacks the sequence and a BRK or IRQ ends up in the NMI handler.
+    fn interrupt(&mut self, b: bool) {
+        self.push16(self.pc);
+        self.push(self.get_flags_byte(b));
+        self.interrupt_disable = true;
+        let vector: u16 = if self.need_nmi {
+            self.need_nmi = false;
+            NMI_VECTOR
+        } else if b {
+            BRK_VECTOR
+        } else {
+            IRQ_VECTOR
+        };
+        let low: u8 = self.read(vector);
+        let high: u8 = self.read(vector.wrapping_add(1));
+        self.pc = ((high as u16) << 8) | (low as u16);
+        // The handler's first instruction always runs before another NMI is taken
+        self.prev_need_nmi = false;
+    }
+
+    // The CPU samples its interrupt lines on every cycle, but only acts on what it saw by the second-to-last cycle of an instruction
+    fn poll_interrupts(&mut self) {
+        self.prev_need_nmi = self.need_nmi;
+        if self.bus.nmi() {
+            self.need_nmi = true;
+        }
+        self.prev_run_irq = self.run_irq;
+        self.run_irq = self.bus.irq() && !self.interrupt_disable;
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+        self.bus.tick();
+    }
+
+    // Every bus access the CPU makes takes one cycle, which the rest of the system runs alongside
+    fn read(&mut self, addr: u16) -> u8 {
+        let halted_cycles: u64 = self.bus.dma();
+        self.cycles += halted_cycles;
+        self.dma_cycles += halted_cycles;
+        self.tick();
+        let val: u8 = self.bus.read(addr);
+        self.poll_interrupts();
+        val
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.tick();
+        self.bus.write(addr, val);
+        self.poll_interrupts();
+    }
+
+    // Reads the next byte of the instruction stream
+    fn fetch(&mut self) -> u8 {
+        let val: u8 = self.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        val
+    }
+
+    fn fetch16(&mut self) -> u16 {
+        let low: u8 = self.fetch();
+        let high: u8 = self.fetch();
+        ((high as u16) << 8) | (low as u16)
+    }
+
+    // Instructions without an operand still read the byte after their opcode, and throw it away
+    fn idle(&mut self) {
+        self.read(self.pc);
+    }
+
+    // The CPU reads the top of the stack, and throws it away, while it increments S
+    fn idle_stack(&mut self) {
+        self.read((self.s as u16).wrapping_add(0x100));
+    }
+
+    // Pointers in the zero page wrap around within it
+    fn read_zero_page_pointer(&mut self, ptr: u8) -> u16 {
+        let low: u8 = self.read(ptr as u16);
+        let high: u8 = self.read(ptr.wrapping_add(1) as u16);
+        ((high as u16) << 8) | (low as u16)
+    }
+
+    // Indexing only carries into the high byte of the address a cycle later, so the CPU first reads from the uncarried address.
+    // Reads skip that cycle when there's nothing to carry, but writes can't take back a write to the wrong address, so they never do.
+    fn index(&mut self, base: u16, index: u8, class: Class) -> u16 {
+        let addr: u16 = base.wrapping_add(index as u16);
+        if class != Class::Read || addr & 0xff00 != base & 0xff00 {
+            self.read((base & 0xff00) | (addr & 0x00ff));
+        }
+        addr
+    }
+
+    // Fetches the operand and works out the address it refers to, making the same bus accesses as the CPU along the way
+    fn operand_addr(&mut self, mode: AddressingMode, class: Class) -> u16 {
+        match mode {
+            AddressingMode::Immediate => {
+                let addr: u16 = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                addr
+            }
+            AddressingMode::ZeroPage => self.fetch() as u16,
+            AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => {
+                let base: u8 = self.fetch();
+                self.read(base as u16);
+                base.wrapping_add(if mode == AddressingMode::ZeroPageX { self.x } else { self.y }) as u16
+            }
+            AddressingMode::Absolute => self.fetch16(),
+            AddressingMode::AbsoluteX => {
+                let base: u16 = self.fetch16();
+                self.index(base, self.x, class)
+            }
+            AddressingMode::AbsoluteY => {
+                let base: u16 = self.fetch16();
+                self.index(base, self.y, class)
+            }
+            AddressingMode::IndirectX => {
+                let ptr: u8 = self.fetch();
+                self.read(ptr as u16);
+                self.read_zero_page_pointer(ptr.wrapping_add(self.x))
+            }
+            AddressingMode::IndirectY => {
+                let ptr: u8 = self.fetch();
+                let base: u16 = self.read_zero_page_pointer(ptr);
+                self.index(base, self.y, class)
+            }
+            AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Indirect | AddressingMode::Relative => unreachable!("{:?} operands aren't addresses", mode),
+        }
+    }
+
+    fn read_operand(&mut self, mode: AddressingMode) -> u8 {
+        let addr: u16 = self.operand_addr(mode, Class::Read);
+        self.read(addr)
+    }
+
+    fn write_operand(&mut self, mode: AddressingMode, val: u8) {
+        let addr: u16 = self.operand_addr(mode, Class::Write);
+        self.write(addr, val);
+    }
+
+    // Read-modify-write instructions write the unmodified value back while they work out the new one
+    fn modify_operand(&mut self, mode: AddressingMode, operation: fn(&mut Self, u8) -> u8) -> u8 {
+        let addr: u16 = self.operand_addr(mode, Class::ReadModifyWrite);
+        let val: u8 = self.read(addr);
+        self.write(addr, val);
+        let result: u8 = operation(self, val);
+        self.write(addr, result);
+        result
+    }
+
+    /// Runs one instruction, or the interrupt sequence in its place, a bus access at a time
+    pub fn step(&mut self) {
+        if self.prev_need_nmi || self.prev_run_irq {
+            // IRQ and NMI fetch an opcode and operand like BRK does, but throw them away
+            self.idle();
+            self.idle();
+            self.interrupt(false);
+            return;
+        }
+
+        if self.trace {
+            self.trace();
+        }
+
+        let start_cycle: u64 = self.cycles - self.dma_cycles;
+
+        // All 6502 instructions begin with a 1-byte opcode
+        let opcode: u8 = self.fetch();
+        let Opcode { mnemonic, mode, cycles, page_cross_penalty, class, .. } = OPCODES[opcode as usize];
+        match class {
+            Class::Read => {
+                let val: u8 = self.read_operand(mode);
+                self.execute_read(mnemonic, val);
+            }
+            Class::Write => self.execute_write(mnemonic, mode),
+            Class::ReadModifyWrite => self.execute_read_modify_write(mnemonic, mode),
+            Class::Other => self.execute_other(mnemonic, mode),
+        }
+
+        // Timing comes from the bus accesses each instruction makes, so check it against the table
+        let max_extra_cycles: u64 = if !page_cross_penalty {
+            0
+        } else if mode == AddressingMode::Relative {
+            2
+        } else {
+            1
+        };
+        debug_assert!((cycles as u64..=cycles as u64 + max_extra_cycles).contains(&(self.cycles - self.dma_cycles - start_cycle)), "{} took the wrong number of cycles", mnemonic);
+    }
+
+    fn execute_read(&mut self, mnemonic: Mnemonic, val: u8) {
+        match mnemonic {
+            Mnemonic::Adc => self.a = self.adc(val),
+            Mnemonic::And => self.a = self.and(val),
+            Mnemonic::Bit => self.bit(val),
+            Mnemonic::Cmp => self.cmp(self.a, val),
+            Mnemonic::Cpx => self.cmp(self.x, val),
+            Mnemonic::Cpy => self.cmp(self.y, val),
+            Mnemonic::Eor => self.a = self.eor(val),
+            Mnemonic::Lda => {
+                self.a = val;
+                self.update_nz_flags(self.a);
+            }
+            Mnemonic::Ldx => {
+                self.x = val;
+                self.update_nz_flags(self.x);
+            }
+            Mnemonic::Ldy => {
+                self.y = val;
+                self.update_nz_flags(self.y);
+            }
+            Mnemonic::Nop => {}
+            Mnemonic::Ora => self.a = self.ora(val),
+            Mnemonic::Sbc => self.a = self.sbc(val),
+
+            // AND, then LSR A
+            Mnemonic::Alr => {
+                let result: u8 = self.and(val);
+                self.a = self.lsr(result);
+            }
+            // AND, then copy N into C
+            Mnemonic::Anc => {
+                self.a = self.and(val);
+                self.carry = self.negative;
+            }
+            // AND, then ROR A with C and V taken from bits 6 and 5
+            Mnemonic::Arr => {
+                let result: u8 = self.and(val);
+                self.a = self.ror(result);
+                self.carry = (self.a & 0b01000000) != 0;
+                self.overflow = ((self.a >> 6) ^ (self.a >> 5)) & 1 != 0;
+            }
+            // X = A & X minus the operand, without borrow
+            Mnemonic::Axs => {
+                let and_result: u8 = self.a & self.x;
+                self.carry = and_result >= val;
+                self.x = and_result.wrapping_sub(val);
+                self.update_nz_flags(self.x);
+            }
+            // A = X = S = the operand & S
+            Mnemonic::Las => {
+                self.s &= val;
+                self.a = self.s;
+                self.x = self.s;
+                self.update_nz_flags(self.s);
+            }
+            // LDA and LDX at once
+            Mnemonic::Lax => {
+                self.a = val;
+                self.x = val;
+                self.update_nz_flags(val);
+            }
+            // Unstable, A = X = (A | magic) & the operand
+            Mnemonic::Lxa => {
+                self.a = (self.a | UNSTABLE_MAGIC) & val;
+                self.x = self.a;
+                self.update_nz_flags(self.a);
+            }
+            // Unstable, A = (A | magic) & X & the operand
+            Mnemonic::Xaa => {
+                self.a = (self.a | UNSTABLE_MAGIC) & self.x & val;
+                self.update_nz_flags(self.a);
+            }
+            _ => unreachable!("{} isn't a read instruction", mnemonic),
+        }
+    }
+
+    fn execute_write(&mut self, mnemonic: Mnemonic, mode: AddressingMode) {
+        match mnemonic {
+            Mnemonic::Sta => self.write_operand(mode, self.a),
+            Mnemonic::Stx => self.write_operand(mode, self.x),
+            Mnemonic::Sty => self.write_operand(mode, self.y),
+            Mnemonic::Sax => self.write_operand(mode, self.a & self.x),
+            Mnemonic::Sha => self.unstable_store(mode, self.a & self.x),
+            Mnemonic::Shx => self.unstable_store(mode, self.x),
+            Mnemonic::Shy => self.unstable_store(mode, self.y),
+            // S = A & X, then stores S like SHA
+            Mnemonic::Tas => {
+                self.s = self.a & self.x;
+                self.unstable_store(mode, self.s);
+            }
+            _ => unreachable!("{} isn't a write instruction", mnemonic),
+        }
+    }
+
+    fn execute_read_modify_write(&mut self, mnemonic: Mnemonic, mode: AddressingMode) {
+        let operation: fn(&mut Self, u8) -> u8 = match mnemonic {
+            Mnemonic::Asl | Mnemonic::Slo => Self::asl,
+            Mnemonic::Dec | Mnemonic::Dcp => Self::dec,
+            Mnemonic::Inc | Mnemonic::Isc => Self::inc,
+            Mnemonic::Lsr | Mnemonic::Sre => Self::lsr,
+            Mnemonic::Rol | Mnemonic::Rla => Self::rol,
+            Mnemonic::Ror | Mnemonic::Rra => Self::ror,
+            _ => unreachable!("{} isn't a read-modify-write instruction", mnemonic),
+        };
+        let result: u8 = if mode == AddressingMode::Accumulator {
+            self.idle();
+            self.a = operation(self, self.a);
+            self.a
+        } else {
+            self.modify_operand(mode, operation)
+        };
+
+        // The unofficial ones go on to combine the result with A
+        match mnemonic {
+            Mnemonic::Dcp => self.cmp(self.a, result),
+            Mnemonic::Isc => self.a = self.sbc(result),
+            Mnemonic::Rla => self.a = self.and(result),
+            Mnemonic::Rra => self.a = self.adc(result),
+            Mnemonic::Slo => self.a = self.ora(result),
+            Mnemonic::Sre => self.a = self.eor(result),
+            _ => {}
+        }
+    }
+
+    fn execute_other(&mut self, mnemonic: Mnemonic, mode: AddressingMode) {
+        match mnemonic {
+            Mnemonic::Bcc => self.branch(!self.carry),
+            Mnemonic::Bcs => self.branch(self.carry),
+            Mnemonic::Beq => self.branch(self.zero),
+            Mnemonic::Bmi => self.branch(self.negative),
+            Mnemonic::Bne => self.branch(!self.zero),
+            Mnemonic::Bpl => self.branch(!self.negative),
+            Mnemonic::Bvc => self.branch(!self.overflow),
+            Mnemonic::Bvs => self.branch(self.overflow),
+            Mnemonic::Brk => {
+                self.fetch();
+                self.interrupt(true);
+            }
+            Mnemonic::Jmp if mode == AddressingMode::Absolute => self.pc = self.fetch16(),
+            Mnemonic::Jmp => {
+                let indirect_addr: u16 = self.fetch16();
+                // The pointer's high byte is read from the start of the same page when the pointer sits at the end of one
+                let low: u8 = self.read(indirect_addr);
+                let high: u8 = self.read((indirect_addr & 0xff00) | ((indirect_addr as u8).wrapping_add(1) as u16));
+                self.pc = ((high as u16) << 8) | (low as u16);
+            }
+            Mnemonic::Jsr => {
+                let low: u8 = self.fetch();
+                self.idle_stack();
+                self.push16(self.pc);
+                let high: u8 = self.fetch();
+                self.pc = ((high as u16) << 8) | (low as u16);
+            }
+            Mnemonic::Rti => {
+                self.idle();
+                self.idle_stack();
+                self.pop_flags();
+                self.pc = self.pop16();
+            }
+            Mnemonic::Rts => {
+                self.idle();
+                self.idle_stack();
+                self.pc = self.pop16();
+                self.fetch();
+            }
+            // Locks up the CPU, which fetches the same opcode forever
+            Mnemonic::Jam => {
+                self.pc = self.pc.wrapping_sub(1);
+                self.idle();
+            }
+            _ => {
+                self.idle();
+                self.execute_implied(mnemonic);
+            }
+        }
+    }
+
+    // Instructions that spend their second cycle on a dummy read, then operate on registers and the stack
+    fn execute_implied(&mut self, mnemonic: Mnemonic) {
+        match mnemonic {
+            Mnemonic::Clc => self.carry = false,
+            Mnemonic::Cld => self.decimal_mode = false,
+            Mnemonic::Cli => self.interrupt_disable = false,
+            Mnemonic::Clv => self.overflow = false,
+            Mnemonic::Sec => self.carry = true,
+            Mnemonic::Sed => self.decimal_mode = true,
+            Mnemonic::Sei => self.interrupt_disable = true,
+            Mnemonic::Dex => self.x = self.dec(self.x),
+            Mnemonic::Dey => self.y = self.dec(self.y),
+            Mnemonic::Inx => self.x = self.inc(self.x),
+            Mnemonic::Iny => self.y = self.inc(self.y),
+            Mnemonic::Nop => {}
+            Mnemonic::Tax => {
+                self.x = self.a;
+                self.update_nz_flags(self.x);
+            }
+            Mnemonic::Tay => {
+                self.y = self.a;
+                self.update_nz_flags(self.y);
+            }
+            Mnemonic::Tsx => {
+                self.x = self.s;
+                self.update_nz_flags(self.x);
+            }
+            Mnemonic::Txa => {
+                self.a = self.x;
+                self.update_nz_flags(self.a);
+            }
+            Mnemonic::Txs => self.s = self.x,
+            Mnemonic::Tya => {
+                self.a = self.y;
+                self.update_nz_flags(self.a);
+            }
+            Mnemonic::Pha => self.push(self.a),
+            Mnemonic::Php => self.push(self.get_flags_byte(true)),
+            Mnemonic::Pla => {
+                self.idle_stack();
+                self.a = self.pop();
+                self.update_nz_flags(self.a);
+            }
+            Mnemonic::Plp => {
+                self.idle_stack();
+                self.pop_flags();
+            }
+            _ => unreachable!("{} isn't an implied instruction", mnemonic),
+        }
+    }
+
+    // Prints the instruction about to run and the state before it, in the style of nestest's log
+    fn trace(&self) {
+        let bytes: [u8; 3] = [self.bus.peek(self.pc), self.bus.peek(self.pc.wrapping_add(1)), self.bus.peek(self.pc.wrapping_add(2))];
+        let opcode: Opcode = OPCODES[bytes[0] as usize];
+        let hex: Vec<String> = bytes[..=opcode.mode.operand_len() as usize].iter().map(|byte: &u8| format!("{:02X}", byte)).collect();
+        print!("{:04X}  {:<8} {}{:<31} ", self.pc, hex.join(" "), if opcode.official { ' ' } else { '*' }, opcodes::disassemble(self.pc, bytes));
+        self.dump_regs();
+    }
+}
+
+fn is_negative(val: u8) -> bool {
+    val & 0b10000000 != 0
+}
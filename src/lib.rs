@@ -0,0 +1,13 @@
+mod apu;
+mod audio;
+mod cpu;
+mod nes;
+mod opcodes;
+mod palette;
+mod ppu;
+
+pub use apu::{Mixer, CHANNEL_COUNT, CHANNEL_NAMES, MAX_CHANNEL_VOLUME};
+pub use cpu::{Bus, Cpu6502};
+pub use nes::{Nes, RomError, BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START, BUTTON_UP};
+pub use palette::{pixel_rgb, SYSTEM_PALETTE};
+pub use ppu::{Mirroring, SCREEN_HEIGHT, SCREEN_WIDTH};
@@ -0,0 +1,586 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+
+use crate::apu::{self, Apu, Mixer, APU_FRAME_COUNTER, APU_STATUS, CHANNEL_COUNT};
+use crate::audio::{Resampler, DEFAULT_SAMPLE_RATE};
+use crate::cpu::{Bus, Cpu6502};
+use crate::ppu::{Mirroring, Ppu, OAMDATA};
+
+// "NES" followed by an MS-DOS end-of-file
+const INES_MAGIC: [u8; 4] = [0x4e, 0x45, 0x53, 0x1a];
+
+/// Why a ROM file couldn't be loaded
+#[derive(Debug)]
+pub enum RomError {
+    /// Reading the file failed for a reason other than it ending early
+    Io(io::Error),
+    /// The file doesn't start with the iNES magic number, so it probably isn't a ROM
+    InvalidMagic,
+    /// The file ends before the end of the header
+    TruncatedHeader,
+    /// The file ends before the end of the trainer
+    TruncatedTrainer,
+    /// The file ends before the end of the PRG ROM
+    TruncatedPrgRom,
+    /// The file ends before the end of the CHR ROM
+    TruncatedChrRom,
+    /// The header is in a format, or uses a part of NES 2.0, that can't be read
+    UnsupportedHeaderVersion,
+    /// Only NROM cartridges, which have no mapper, are supported
+    UnsupportedMapper(u16),
+    /// In 16 KiB units. NROM has 16 or 32 KiB.
+    UnsupportedPrgRomSize(u16),
+    /// In 8 KiB units. NROM has 8 KiB, or CHR RAM instead.
+    UnsupportedChrRomSize(u16),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(err) => write!(f, "couldn't read the file: {}", err),
+            RomError::InvalidMagic => write!(f, "not an iNES ROM"),
+            RomError::TruncatedHeader => write!(f, "the header is cut short"),
+            RomError::TruncatedTrainer => write!(f, "the trainer is cut short"),
+            RomError::TruncatedPrgRom => write!(f, "the PRG ROM is cut short"),
+            RomError::TruncatedChrRom => write!(f, "the CHR ROM is cut short"),
+            RomError::UnsupportedHeaderVersion => write!(f, "the header is in a format that isn't supported"),
+            RomError::UnsupportedMapper(mapper) => write!(f, "mapper {} isn't supported, only NROM (mapper 0) is", mapper),
+            RomError::UnsupportedPrgRomSize(size) => write!(f, "{} KiB of PRG ROM isn't supported, only 16 or 32 KiB is", *size as u32 * 16),
+            RomError::UnsupportedChrRomSize(size) => write!(f, "{} KiB of CHR ROM isn't supported, only up to 8 KiB is", *size as u32 * 8),
+        }
+    }
+}
+
+impl Error for RomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RomError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// Fills `buf` from the file, reporting the file ending early as `truncated`
+fn read_section(rom_file: &mut impl Read, buf: &mut [u8], truncated: RomError) -> Result<(), RomError> {
+    rom_file.read_exact(buf).map_err(|err: io::Error| if err.kind() == ErrorKind::UnexpectedEof { truncated } else { RomError::Io(err) })
+}
+
+/// The A button's bit in a controller's state
+pub const BUTTON_A: u8 = 0b00000001;
+/// The B button's bit in a controller's state
+pub const BUTTON_B: u8 = 0b00000010;
+/// The Select button's bit in a controller's state
+pub const BUTTON_SELECT: u8 = 0b00000100;
+/// The Start button's bit in a controller's state
+pub const BUTTON_START: u8 = 0b00001000;
+/// Up on the D-pad's bit in a controller's state
+pub const BUTTON_UP: u8 = 0b00010000;
+/// Down on the D-pad's bit in a controller's state
+pub const BUTTON_DOWN: u8 = 0b00100000;
+/// Left on the D-pad's bit in a controller's state
+pub const BUTTON_LEFT: u8 = 0b01000000;
+/// Right on the D-pad's bit in a controller's state
+pub const BUTTON_RIGHT: u8 = 0b10000000;
+
+// The NES's memory map and everything on it besides the CPU
+struct NesBus {
+    // Counts CPU cycles, including those the CPU spends halted
+    cycles: u64,
+    // One bit per source currently asserting the IRQ line
+    irq_line: u8,
+    // The page to copy into OAM before the CPU's next read cycle
+    oam_dma_page: Option<u8>,
+
+    ram: [u8; 0x800],
+    apu_and_io_regs: [u8; 0x18],
+    cartridge: [u8; 0xbfe0],
+
+    ppu: Ppu,
+    apu: Apu,
+    mixer: Mixer,
+    audio: Resampler,
+    // One per channel, only while recording stems
+    stem_audio: Vec<Resampler>,
+
+    // One bit per button for each controller, in the order they're read out
+    buttons: [u8; 2],
+    current_button: [usize; 2],
+    strobe_mode: bool,
+}
+
+impl Default for NesBus {
+    fn default() -> NesBus {
+        NesBus {
+            cycles: 0,
+            irq_line: 0,
+            oam_dma_page: None,
+            ram: [0; 0x800],
+            apu_and_io_regs: [0; 0x18],
+            cartridge: [0; 0xbfe0],
+            ppu: Default::default(),
+            apu: Apu::new(),
+            mixer: Default::default(),
+            audio: Default::default(),
+            stem_audio: Vec::new(),
+            buttons: [0; 2],
+            current_button: [0; 2],
+            strobe_mode: false,
+        }
+    }
+}
+
+// Sources of the IRQ line
+const IRQ_FRAME_COUNTER: u8 = 0b01;
+const IRQ_DMC: u8 = 0b10;
+const OAMDMA: u16 = 0x4014;
+const OAMDMA_I: u16 = OAMDMA - 0x4000;
+const JOYPAD_1: u16 = 0x4016;
+const JOYPAD_1_I: u16 = JOYPAD_1 - 0x4000;
+// Shares its address with the APU's frame counter, which is write-only
+const JOYPAD_2: u16 = 0x4017;
+const JOYPAD_2_I: u16 = JOYPAD_2 - 0x4000;
+const APU_STATUS_I: u16 = APU_STATUS - 0x4000;
+const APU_FRAME_COUNTER_I: u16 = APU_FRAME_COUNTER - 0x4000;
+// The CPU is halted for this long while the DMC fetches a sample byte
+const DMC_STALL_CYCLES: u64 = 4;
+
+impl NesBus {
+    // Parses an iNES or NES 2.0 file into a freshly powered-on system with the cartridge plugged in
+    fn new(rom_file: &mut impl Read) -> Result<Self, RomError> {
+        let mut result: Self = Default::default();
+
+        let mut header: [u8; 16] = [0; 16];
+        read_section(rom_file, &mut header, RomError::TruncatedHeader)?;
+        if header[0..4] != INES_MAGIC {
+            return Err(RomError::InvalidMagic);
+        }
+        let flags_6: u8 = header[6];
+        let flags_7: u8 = header[7];
+
+        // NES 2.0 extends the mapper number and ROM sizes into bytes 8 and 9, which are unused by iNES.
+        // Anything else is treated as archaic iNES, from before the header settled, where tools like DiskDude! wrote junk from
+        // flags 7 onwards. Only the low nibble of the mapper number can be trusted there.
+        let (mapper, prg_rom_size, chr_rom_size): (u16, u16, u16) = match flags_7 & 0b1100 {
+            0b0000 if header[12..16] == [0; 4] => ((flags_7 & 0xf0) as u16 | (flags_6 >> 4) as u16, header[4] as u16, header[5] as u16),
+            // A size nibble of 0xf switches that ROM's size to an exponent-multiplier notation, which isn't read
+            0b1000 if (header[9] & 0x0f) == 0x0f || (header[9] >> 4) == 0x0f => return Err(RomError::UnsupportedHeaderVersion),
+            0b1000 => (
+                ((header[8] & 0x0f) as u16) << 8 | (flags_7 & 0xf0) as u16 | (flags_6 >> 4) as u16,
+                ((header[9] & 0x0f) as u16) << 8 | header[4] as u16,
+                ((header[9] >> 4) as u16) << 8 | header[5] as u16,
+            ),
+            // No version of the header sets both bits
+            0b1100 => return Err(RomError::UnsupportedHeaderVersion),
+            _ => ((flags_6 >> 4) as u16, header[4] as u16, header[5] as u16),
+        };
+        if mapper != 0 {
+            return Err(RomError::UnsupportedMapper(mapper));
+        }
+
+        // Trainers were for copiers' own hardware and go unused on NROM
+        if (flags_6 & 0b100) != 0 {
+            let mut trainer: [u8; 0x200] = [0; 0x200];
+            read_section(rom_file, &mut trainer, RomError::TruncatedTrainer)?;
+        }
+
+        if !(1..=2).contains(&prg_rom_size) {
+            return Err(RomError::UnsupportedPrgRomSize(prg_rom_size));
+        }
+        for prg_rom_no in 0..prg_rom_size {
+            let mut buf: [u8; 0x4000] = [0; 0x4000];
+            read_section(rom_file, &mut buf, RomError::TruncatedPrgRom)?;
+            for (i, &byte) in buf.iter().enumerate() {
+                result.write((if prg_rom_size == 2 { 0x8000 } else { 0xc000 }) + prg_rom_no * 0x4000 + i as u16, byte);
+            }
+        }
+
+        if chr_rom_size > 1 {
+            return Err(RomError::UnsupportedChrRomSize(chr_rom_size));
+        }
+        for chr_rom_no in 0..chr_rom_size {
+            let mut buf: [u8; 0x2000] = [0; 0x2000];
+            read_section(rom_file, &mut buf, RomError::TruncatedChrRom)?;
+            for (i, &byte) in buf.iter().enumerate() {
+                result.ppu.chr[chr_rom_no as usize * 0x2000 + i] = byte;
+            }
+        }
+        // Cartridges without CHR ROM have 8 KiB of CHR RAM instead
+        result.ppu.chr_is_ram = chr_rom_size == 0;
+
+        result.ppu.mirroring = if (flags_6 & 0b1000) != 0 {
+            Mirroring::FourScreen
+        } else if (flags_6 & 0b1) != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+
+        Ok(result)
+    }
+
+    // Shifts out the next button of a controller's report
+    fn read_controller(&mut self, port: usize) -> u8 {
+        let result: u8 = (self.buttons[port] >> self.current_button[port]) & 1;
+        if !self.strobe_mode {
+            self.current_button[port] = (self.current_button[port] + 1) % 8;
+        }
+        result
+    }
+
+    // Asserts or releases one source's hold on the IRQ line. The line stays asserted as long as any source holds it.
+    fn set_irq(&mut self, source: u8, asserted: bool) {
+        if asserted {
+            self.irq_line |= source;
+        } else {
+            self.irq_line &= !source;
+        }
+    }
+
+    fn dmc_dma(&mut self, addr: u16) {
+        for _ in 0..DMC_STALL_CYCLES {
+            self.tick();
+        }
+        let sample: u8 = self.read(addr);
+        self.apu.dmc_load_sample(sample);
+    }
+
+    // Copies a page of memory into OAM through OAMDATA, a byte every two cycles.
+    // The copy has to start on an even cycle, so it takes an extra cycle when it doesn't.
+    fn oam_dma(&mut self, page: u8) {
+        self.tick();
+        if self.cycles % 2 == 1 {
+            self.tick();
+        }
+        for i in 0x00..=0xff {
+            self.tick();
+            let val: u8 = self.read(((page as u16) << 8) | i);
+            self.tick();
+            self.write(OAMDATA, val);
+        }
+    }
+}
+
+impl Bus for NesBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        // This function needs `&mut self` because reading from some memory-mapped registers can change
+        // the state of the system
+        match addr {
+            0x0000..0x2000 => self.ram[(addr % 0x0800) as usize],
+            0x2000..0x4000 => self.ppu.read_register(addr % 8),
+            0x4000..0x4018 => match addr - 0x4000 {
+                JOYPAD_1_I => self.read_controller(0),
+                JOYPAD_2_I => self.read_controller(1),
+                APU_STATUS_I => self.apu.read_status(),
+                _ => self.apu_and_io_regs[(addr - 0x4000) as usize],
+            },
+            0x4018..0x4020 => 0,
+            0x4020..=0xffff => self.cartridge[(addr - 0x4020) as usize],
+        }
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..0x2000 => self.ram[(addr % 0x0800) as usize] = val,
+            0x2000..0x4000 => self.ppu.write_register(addr % 8, val),
+            0x4000..0x4018 => match addr - 0x4000 {
+                // The copy itself happens once the CPU can be halted
+                OAMDMA_I => self.oam_dma_page = Some(val),
+                JOYPAD_1_I => {
+                    if val & 0b1 > self.strobe_mode as u8 {
+                        // Entering strobe_mode
+                        self.current_button = [0; 2];
+                        self.strobe_mode = true;
+                    } else if val & 0b1 < self.strobe_mode as u8 {
+                        // Leaving strobe_mode
+                        self.strobe_mode = false;
+                    }
+                    self.apu_and_io_regs[(addr - 0x4000) as usize] = val & 0b111
+                }
+                0x00..=0x13 | APU_STATUS_I | APU_FRAME_COUNTER_I => self.apu.write_register(addr, val),
+                _ => self.apu_and_io_regs[(addr - 0x4000) as usize] = val,
+            },
+            0x4018..0x4020 => {}
+            0x4020..=0xffff => self.cartridge[(addr - 0x4020) as usize] = val,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.cycles += 1;
+        for _ in 0..3 {
+            self.ppu.tick();
+        }
+        self.apu.tick();
+        let outputs: [u8; CHANNEL_COUNT] = self.apu.channel_outputs();
+        self.audio.push(self.mixer.mix(outputs));
+        for (channel, stem_audio) in self.stem_audio.iter_mut().enumerate() {
+            stem_audio.push(apu::mix_channel(channel, outputs));
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..0x2000 => self.ram[(addr % 0x0800) as usize],
+            0x4020..=0xffff => self.cartridge[(addr - 0x4020) as usize],
+            _ => 0,
+        }
+    }
+
+    fn nmi(&mut self) -> bool {
+        std::mem::take(&mut self.ppu.nmi_pending)
+    }
+
+    fn irq(&mut self) -> bool {
+        self.set_irq(IRQ_FRAME_COUNTER, self.apu.frame_irq());
+        self.set_irq(IRQ_DMC, self.apu.dmc_irq());
+        self.irq_line != 0
+    }
+
+    fn dma(&mut self) -> u64 {
+        let start_cycle: u64 = self.cycles;
+        if let Some(page) = self.oam_dma_page.take() {
+            self.oam_dma(page);
+        }
+        if let Some(sample_addr) = self.apu.dmc_sample_request() {
+            self.dmc_dma(sample_addr);
+        }
+        self.cycles - start_cycle
+    }
+}
+
+/// A whole console with a cartridge plugged in
+pub struct Nes {
+    cpu: Cpu6502<NesBus>,
+    sample_rate: f64,
+}
+
+impl Nes {
+    /// Plugs an iNES or NES 2.0 ROM into a freshly powered-on console
+    pub fn new(rom_file: &mut impl Read) -> Result<Nes, RomError> {
+        Ok(Nes {
+            cpu: Cpu6502::new(NesBus::new(rom_file)?),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+        })
+    }
+
+    /// Swaps in another cartridge and powers the console back on. The console is left alone if the ROM can't be loaded.
+    pub fn load_rom(&mut self, rom_file: &mut impl Read) -> Result<(), RomError> {
+        self.power_on(NesBus::new(rom_file)?);
+        Ok(())
+    }
+
+    /// Runs until the PPU has produced a full frame
+    pub fn run_frame(&mut self) {
+        while !self.cpu.bus.ppu.frame_complete {
+            self.cpu.step();
+        }
+        self.cpu.bus.ppu.frame_complete = false;
+    }
+
+    /// The last frame, as system palette indices with the emphasis bits above them. pixel_rgb converts them to RGB.
+    pub fn framebuffer(&self) -> &[u16] {
+        &self.cpu.bus.ppu.framebuffer
+    }
+
+    /// Takes the samples produced since the last call, at the sample rate
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        self.cpu.bus.audio.take_samples()
+    }
+
+    /// Takes each channel's samples produced since the last call, while stems are being recorded
+    pub fn stem_samples(&mut self) -> Vec<Vec<f32>> {
+        self.cpu.bus.stem_audio.iter_mut().map(Resampler::take_samples).collect()
+    }
+
+    /// In Hz
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.cpu.bus.audio = Resampler::new(sample_rate);
+        for stem_audio in self.cpu.bus.stem_audio.iter_mut() {
+            *stem_audio = Resampler::new(sample_rate);
+        }
+    }
+
+    /// Nudges the sample rate by a fraction of a percent to keep an audio queue near `target` samples
+    pub fn adjust_audio_rate(&mut self, queued: usize, target: usize) {
+        self.cpu.bus.audio.adjust_rate(queued, target);
+        for stem_audio in self.cpu.bus.stem_audio.iter_mut() {
+            stem_audio.adjust_rate(queued, target);
+        }
+    }
+
+    /// Whether each channel is also resampled on its own, for stem_samples
+    pub fn set_recording_stems(&mut self, recording_stems: bool) {
+        self.cpu.bus.stem_audio = if recording_stems { (0..CHANNEL_COUNT).map(|_| Resampler::new(self.sample_rate)).collect() } else { Vec::new() };
+    }
+
+    /// Each channel's volume, mute and solo settings
+    pub fn mixer(&self) -> &Mixer {
+        &self.cpu.bus.mixer
+    }
+
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.cpu.bus.mixer
+    }
+
+    /// How the nametables are mirrored, as set by the ROM's header or since by set_mirroring
+    pub fn mirroring(&self) -> Mirroring {
+        self.cpu.bus.ppu.mirroring
+    }
+
+    /// For mappers, which can switch the mirroring while the game runs
+    pub fn set_mirroring(&mut self, mirroring: Mirroring) {
+        self.cpu.bus.ppu.mirroring = mirroring;
+    }
+
+    /// Sets which buttons are held on the controller in port 0 or 1, as BUTTON_* bits. Other ports don't exist, so they're ignored.
+    pub fn set_buttons(&mut self, port: usize, state: u8) {
+        if let Some(buttons) = self.cpu.bus.buttons.get_mut(port) {
+            *buttons = state;
+        }
+    }
+
+    /// Whether every sprite on a scanline is drawn, rather than only the first eight as on hardware
+    pub fn sprite_limit_disabled(&self) -> bool {
+        self.cpu.bus.ppu.disable_sprite_limit
+    }
+
+    pub fn set_sprite_limit_disabled(&mut self, disabled: bool) {
+        self.cpu.bus.ppu.disable_sprite_limit = disabled;
+    }
+
+    /// Whether to print every instruction as it runs
+    pub fn set_trace(&mut self, trace: bool) {
+        self.cpu.trace = trace;
+    }
+
+    /// Presses the reset button, which leaves memory alone
+    pub fn reset(&mut self) {
+        self.cpu.bus.oam_dma_page = None;
+        self.cpu.bus.ppu.reset();
+        self.cpu.bus.apu.reset();
+        self.cpu.reset();
+    }
+
+    /// Turns the console off and on again with the same cartridge
+    pub fn power_cycle(&mut self) {
+        let old: &NesBus = &self.cpu.bus;
+        let mut bus: NesBus = NesBus { cartridge: old.cartridge, ..Default::default() };
+        bus.ppu.chr = old.ppu.chr;
+        bus.ppu.chr_is_ram = old.ppu.chr_is_ram;
+        bus.ppu.mirroring = old.ppu.mirroring;
+        self.power_on(bus);
+    }
+
+    // Replaces the CPU and everything on its bus with freshly powered-on ones, keeping the settings above
+    fn power_on(&mut self, mut bus: NesBus) {
+        let old: NesBus = std::mem::take(&mut self.cpu.bus);
+        bus.mixer = old.mixer;
+        bus.audio = old.audio;
+        bus.stem_audio = old.stem_audio;
+        bus.buttons = old.buttons;
+        bus.ppu.disable_sprite_limit = old.ppu.disable_sprite_limit;
+        let trace: bool = self.cpu.trace;
+        self.cpu = Cpu6502::new(bus);
+        self.cpu.trace = trace;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // An iNES file with the given header fields, followed by `data_size` bytes of ROM
+    fn rom(prg_rom_size: u8, chr_rom_size: u8, flags_6: u8, flags_7: u8, data_size: usize) -> Cursor<Vec<u8>> {
+        let mut data: Vec<u8> = INES_MAGIC.to_vec();
+        data.extend_from_slice(&[prg_rom_size, chr_rom_size, flags_6, flags_7]);
+        data.resize(16 + data_size, 0);
+        Cursor::new(data)
+    }
+
+    fn load(mut rom_file: Cursor<Vec<u8>>) -> Result<Nes, RomError> {
+        Nes::new(&mut rom_file)
+    }
+
+    #[test]
+    fn loads_nrom() {
+        assert!(load(rom(1, 1, 0, 0, 0x6000)).is_ok());
+        assert!(load(rom(2, 0, 0, 0, 0x8000)).is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_file() {
+        assert!(matches!(load(Cursor::new(Vec::new())), Err(RomError::TruncatedHeader)));
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0, 0x6000);
+        rom_file.get_mut()[3] = 0;
+        assert!(matches!(load(rom_file), Err(RomError::InvalidMagic)));
+    }
+
+    #[test]
+    fn rejects_truncated_sections() {
+        assert!(matches!(load(rom(1, 1, 0b100, 0, 0x100)), Err(RomError::TruncatedTrainer)));
+        assert!(matches!(load(rom(2, 1, 0, 0, 0x4000)), Err(RomError::TruncatedPrgRom)));
+        assert!(matches!(load(rom(1, 1, 0, 0, 0x5000)), Err(RomError::TruncatedChrRom)));
+    }
+
+    #[test]
+    fn skips_trainer() {
+        assert!(load(rom(1, 1, 0b100, 0, 0x200 + 0x6000)).is_ok());
+    }
+
+    #[test]
+    fn rejects_other_mappers() {
+        assert!(matches!(load(rom(1, 1, 0x10, 0, 0x6000)), Err(RomError::UnsupportedMapper(1))));
+        assert!(matches!(load(rom(1, 1, 0x40, 0x40, 0x6000)), Err(RomError::UnsupportedMapper(0x44))));
+    }
+
+    #[test]
+    fn reads_nes_2_0_sizes() {
+        assert!(load(rom(1, 1, 0, 0b1000, 0x6000)).is_ok());
+
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0b1000, 0x6000);
+        rom_file.get_mut()[9] = 0x01;
+        assert!(matches!(load(rom_file), Err(RomError::UnsupportedPrgRomSize(0x101))));
+
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0b1000, 0x6000);
+        rom_file.get_mut()[9] = 0x10;
+        assert!(matches!(load(rom_file), Err(RomError::UnsupportedChrRomSize(0x101))));
+
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0b1000, 0x6000);
+        rom_file.get_mut()[8] = 0x01;
+        assert!(matches!(load(rom_file), Err(RomError::UnsupportedMapper(0x100))));
+    }
+
+    #[test]
+    fn rejects_unsupported_header_versions() {
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0b1000, 0x6000);
+        rom_file.get_mut()[9] = 0x0f;
+        assert!(matches!(load(rom_file), Err(RomError::UnsupportedHeaderVersion)));
+
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0b1000, 0x6000);
+        rom_file.get_mut()[9] = 0xf0;
+        assert!(matches!(load(rom_file), Err(RomError::UnsupportedHeaderVersion)));
+
+        assert!(matches!(load(rom(1, 1, 0, 0b1100, 0x6000)), Err(RomError::UnsupportedHeaderVersion)));
+    }
+
+    #[test]
+    fn ignores_junk_in_archaic_headers() {
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0, 0, 0x6000);
+        rom_file.get_mut()[7..16].copy_from_slice(b"DiskDude!");
+        assert!(load(rom_file).is_ok());
+
+        let mut rom_file: Cursor<Vec<u8>> = rom(1, 1, 0x20, 0, 0x6000);
+        rom_file.get_mut()[7..16].copy_from_slice(b"DiskDude!");
+        assert!(matches!(load(rom_file), Err(RomError::UnsupportedMapper(2))));
+    }
+}
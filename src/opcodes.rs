@@ -0,0 +1,451 @@
+use std::fmt;
+
+use AddressingMode::*;
+use Class::*;
+use Mnemonic::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mnemonic {
+    Adc,
+    And,
+    Asl,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    Jmp,
+    Jsr,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    Ror,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sta,
+    Stx,
+    Sty,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+
+    // Unofficial
+    Alr,
+    Anc,
+    Arr,
+    Axs,
+    Dcp,
+    Isc,
+    Jam,
+    Las,
+    Lax,
+    Lxa,
+    Rla,
+    Rra,
+    Sax,
+    Sha,
+    Shx,
+    Shy,
+    Slo,
+    Sre,
+    Tas,
+    Xaa,
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_uppercase())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl AddressingMode {
+    // How many bytes of operand follow the opcode
+    pub fn operand_len(self) -> u16 {
+        match self {
+            Implied | Accumulator => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | IndirectX | IndirectY | Relative => 1,
+            Absolute | AbsoluteX | AbsoluteY | Indirect => 2,
+        }
+    }
+}
+
+// How an instruction uses its operand, which decides the bus accesses it makes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Class {
+    Read,
+    Write,
+    ReadModifyWrite,
+    // Implied, stack and control flow instructions
+    Other,
+}
+
+#[derive(Clone, Copy)]
+pub struct Opcode {
+    pub mnemonic: Mnemonic,
+    pub mode: AddressingMode,
+    // Not counting page crossings or taken branches
+    pub cycles: u8,
+    // Whether crossing a page costs an extra cycle. For branches, that's on top of the cycle a taken branch costs.
+    pub page_cross_penalty: bool,
+    pub class: Class,
+    pub official: bool,
+}
+
+const fn op(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8, page_cross_penalty: bool, class: Class) -> Opcode {
+    Opcode {
+        mnemonic,
+        mode,
+        cycles,
+        page_cross_penalty,
+        class,
+        official: true,
+    }
+}
+
+const fn unofficial(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8, page_cross_penalty: bool, class: Class) -> Opcode {
+    Opcode {
+        mnemonic,
+        mode,
+        cycles,
+        page_cross_penalty,
+        class,
+        official: false,
+    }
+}
+
+pub const OPCODES: [Opcode; 256] = [
+    op(Brk, Implied, 7, false, Other),                     // 0x00
+    op(Ora, IndirectX, 6, false, Read),                    // 0x01
+    unofficial(Jam, Implied, 2, false, Other),             // 0x02
+    unofficial(Slo, IndirectX, 8, false, ReadModifyWrite), // 0x03
+    unofficial(Nop, ZeroPage, 3, false, Read),             // 0x04
+    op(Ora, ZeroPage, 3, false, Read),                     // 0x05
+    op(Asl, ZeroPage, 5, false, ReadModifyWrite),          // 0x06
+    unofficial(Slo, ZeroPage, 5, false, ReadModifyWrite),  // 0x07
+    op(Php, Implied, 3, false, Other),                     // 0x08
+    op(Ora, Immediate, 2, false, Read),                    // 0x09
+    op(Asl, Accumulator, 2, false, ReadModifyWrite),       // 0x0a
+    unofficial(Anc, Immediate, 2, false, Read),            // 0x0b
+    unofficial(Nop, Absolute, 4, false, Read),             // 0x0c
+    op(Ora, Absolute, 4, false, Read),                     // 0x0d
+    op(Asl, Absolute, 6, false, ReadModifyWrite),          // 0x0e
+    unofficial(Slo, Absolute, 6, false, ReadModifyWrite),  // 0x0f
+    op(Bpl, Relative, 2, true, Other),                     // 0x10
+    op(Ora, IndirectY, 5, true, Read),                     // 0x11
+    unofficial(Jam, Implied, 2, false, Other),             // 0x12
+    unofficial(Slo, IndirectY, 8, false, ReadModifyWrite), // 0x13
+    unofficial(Nop, ZeroPageX, 4, false, Read),            // 0x14
+    op(Ora, ZeroPageX, 4, false, Read),                    // 0x15
+    op(Asl, ZeroPageX, 6, false, ReadModifyWrite),         // 0x16
+    unofficial(Slo, ZeroPageX, 6, false, ReadModifyWrite), // 0x17
+    op(Clc, Implied, 2, false, Other),                     // 0x18
+    op(Ora, AbsoluteY, 4, true, Read),                     // 0x19
+    unofficial(Nop, Implied, 2, false, Other),             // 0x1a
+    unofficial(Slo, AbsoluteY, 7, false, ReadModifyWrite), // 0x1b
+    unofficial(Nop, AbsoluteX, 4, true, Read),             // 0x1c
+    op(Ora, AbsoluteX, 4, true, Read),                     // 0x1d
+    op(Asl, AbsoluteX, 7, false, ReadModifyWrite),         // 0x1e
+    unofficial(Slo, AbsoluteX, 7, false, ReadModifyWrite), // 0x1f
+    op(Jsr, Absolute, 6, false, Other),                    // 0x20
+    op(And, IndirectX, 6, false, Read),                    // 0x21
+    unofficial(Jam, Implied, 2, false, Other),             // 0x22
+    unofficial(Rla, IndirectX, 8, false, ReadModifyWrite), // 0x23
+    op(Bit, ZeroPage, 3, false, Read),                     // 0x24
+    op(And, ZeroPage, 3, false, Read),                     // 0x25
+    op(Rol, ZeroPage, 5, false, ReadModifyWrite),          // 0x26
+    unofficial(Rla, ZeroPage, 5, false, ReadModifyWrite),  // 0x27
+    op(Plp, Implied, 4, false, Other),                     // 0x28
+    op(And, Immediate, 2, false, Read),                    // 0x29
+    op(Rol, Accumulator, 2, false, ReadModifyWrite),       // 0x2a
+    unofficial(Anc, Immediate, 2, false, Read),            // 0x2b
+    op(Bit, Absolute, 4, false, Read),                     // 0x2c
+    op(And, Absolute, 4, false, Read),                     // 0x2d
+    op(Rol, Absolute, 6, false, ReadModifyWrite),          // 0x2e
+    unofficial(Rla, Absolute, 6, false, ReadModifyWrite),  // 0x2f
+    op(Bmi, Relative, 2, true, Other),                     // 0x30
+    op(And, IndirectY, 5, true, Read),                     // 0x31
+    unofficial(Jam, Implied, 2, false, Other),             // 0x32
+    unofficial(Rla, IndirectY, 8, false, ReadModifyWrite), // 0x33
+    unofficial(Nop, ZeroPageX, 4, false, Read),            // 0x34
+    op(And, ZeroPageX, 4, false, Read),                    // 0x35
+    op(Rol, ZeroPageX, 6, false, ReadModifyWrite),         // 0x36
+    unofficial(Rla, ZeroPageX, 6, false, ReadModifyWrite), // 0x37
+    op(Sec, Implied, 2, false, Other),                     // 0x38
+    op(And, AbsoluteY, 4, true, Read),                     // 0x39
+    unofficial(Nop, Implied, 2, false, Other),             // 0x3a
+    unofficial(Rla, AbsoluteY, 7, false, ReadModifyWrite), // 0x3b
+    unofficial(Nop, AbsoluteX, 4, true, Read),             // 0x3c
+    op(And, AbsoluteX, 4, true, Read),                     // 0x3d
+    op(Rol, AbsoluteX, 7, false, ReadModifyWrite),         // 0x3e
+    unofficial(Rla, AbsoluteX, 7, false, ReadModifyWrite), // 0x3f
+    op(Rti, Implied, 6, false, Other),                     // 0x40
+    op(Eor, IndirectX, 6, false, Read),                    // 0x41
+    unofficial(Jam, Implied, 2, false, Other),             // 0x42
+    unofficial(Sre, IndirectX, 8, false, ReadModifyWrite), // 0x43
+    unofficial(Nop, ZeroPage, 3, false, Read),             // 0x44
+    op(Eor, ZeroPage, 3, false, Read),                     // 0x45
+    op(Lsr, ZeroPage, 5, false, ReadModifyWrite),          // 0x46
+    unofficial(Sre, ZeroPage, 5, false, ReadModifyWrite),  // 0x47
+    op(Pha, Implied, 3, false, Other),                     // 0x48
+    op(Eor, Immediate, 2, false, Read),                    // 0x49
+    op(Lsr, Accumulator, 2, false, ReadModifyWrite),       // 0x4a
+    unofficial(Alr, Immediate, 2, false, Read),            // 0x4b
+    op(Jmp, Absolute, 3, false, Other),                    // 0x4c
+    op(Eor, Absolute, 4, false, Read),                     // 0x4d
+    op(Lsr, Absolute, 6, false, ReadModifyWrite),          // 0x4e
+    unofficial(Sre, Absolute, 6, false, ReadModifyWrite),  // 0x4f
+    op(Bvc, Relative, 2, true, Other),                     // 0x50
+    op(Eor, IndirectY, 5, true, Read),                     // 0x51
+    unofficial(Jam, Implied, 2, false, Other),             // 0x52
+    unofficial(Sre, IndirectY, 8, false, ReadModifyWrite), // 0x53
+    unofficial(Nop, ZeroPageX, 4, false, Read),            // 0x54
+    op(Eor, ZeroPageX, 4, false, Read),                    // 0x55
+    op(Lsr, ZeroPageX, 6, false, ReadModifyWrite),         // 0x56
+    unofficial(Sre, ZeroPageX, 6, false, ReadModifyWrite), // 0x57
+    op(Cli, Implied, 2, false, Other),                     // 0x58
+    op(Eor, AbsoluteY, 4, true, Read),                     // 0x59
+    unofficial(Nop, Implied, 2, false, Other),             // 0x5a
+    unofficial(Sre, AbsoluteY, 7, false, ReadModifyWrite), // 0x5b
+    unofficial(Nop, AbsoluteX, 4, true, Read),             // 0x5c
+    op(Eor, AbsoluteX, 4, true, Read),                     // 0x5d
+    op(Lsr, AbsoluteX, 7, false, ReadModifyWrite),         // 0x5e
+    unofficial(Sre, AbsoluteX, 7, false, ReadModifyWrite), // 0x5f
+    op(Rts, Implied, 6, false, Other),                     // 0x60
+    op(Adc, IndirectX, 6, false, Read),                    // 0x61
+    unofficial(Jam, Implied, 2, false, Other),             // 0x62
+    unofficial(Rra, IndirectX, 8, false, ReadModifyWrite), // 0x63
+    unofficial(Nop, ZeroPage, 3, false, Read),             // 0x64
+    op(Adc, ZeroPage, 3, false, Read),                     // 0x65
+    op(Ror, ZeroPage, 5, false, ReadModifyWrite),          // 0x66
+    unofficial(Rra, ZeroPage, 5, false, ReadModifyWrite),  // 0x67
+    op(Pla, Implied, 4, false, Other),                     // 0x68
+    op(Adc, Immediate, 2, false, Read),                    // 0x69
+    op(Ror, Accumulator, 2, false, ReadModifyWrite),       // 0x6a
+    unofficial(Arr, Immediate, 2, false, Read),            // 0x6b
+    op(Jmp, Indirect, 5, false, Other),                    // 0x6c
+    op(Adc, Absolute, 4, false, Read),                     // 0x6d
+    op(Ror, Absolute, 6, false, ReadModifyWrite),          // 0x6e
+    unofficial(Rra, Absolute, 6, false, ReadModifyWrite),  // 0x6f
+    op(Bvs, Relative, 2, true, Other),                     // 0x70
+    op(Adc, IndirectY, 5, true, Read),                     // 0x71
+    unofficial(Jam, Implied, 2, false, Other),             // 0x72
+    unofficial(Rra, IndirectY, 8, false, ReadModifyWrite), // 0x73
+    unofficial(Nop, ZeroPageX, 4, false, Read),            // 0x74
+    op(Adc, ZeroPageX, 4, false, Read),                    // 0x75
+    op(Ror, ZeroPageX, 6, false, ReadModifyWrite),         // 0x76
+    unofficial(Rra, ZeroPageX, 6, false, ReadModifyWrite), // 0x77
+    op(Sei, Implied, 2, false, Other),                     // 0x78
+    op(Adc, AbsoluteY, 4, true, Read),                     // 0x79
+    unofficial(Nop, Implied, 2, false, Other),             // 0x7a
+    unofficial(Rra, AbsoluteY, 7, false, ReadModifyWrite), // 0x7b
+    unofficial(Nop, AbsoluteX, 4, true, Read),             // 0x7c
+    op(Adc, AbsoluteX, 4, true, Read),                     // 0x7d
+    op(Ror, AbsoluteX, 7, false, ReadModifyWrite),         // 0x7e
+    unofficial(Rra, AbsoluteX, 7, false, ReadModifyWrite), // 0x7f
+    unofficial(Nop, Immediate, 2, false, Read),            // 0x80
+    op(Sta, IndirectX, 6, false, Write),                   // 0x81
+    unofficial(Nop, Immediate, 2, false, Read),            // 0x82
+    unofficial(Sax, IndirectX, 6, false, Write),           // 0x83
+    op(Sty, ZeroPage, 3, false, Write),                    // 0x84
+    op(Sta, ZeroPage, 3, false, Write),                    // 0x85
+    op(Stx, ZeroPage, 3, false, Write),                    // 0x86
+    unofficial(Sax, ZeroPage, 3, false, Write),            // 0x87
+    op(Dey, Implied, 2, false, Other),                     // 0x88
+    unofficial(Nop, Immediate, 2, false, Read),            // 0x89
+    op(Txa, Implied, 2, false, Other),                     // 0x8a
+    unofficial(Xaa, Immediate, 2, false, Read),            // 0x8b
+    op(Sty, Absolute, 4, false, Write),                    // 0x8c
+    op(Sta, Absolute, 4, false, Write),                    // 0x8d
+    op(Stx, Absolute, 4, false, Write),                    // 0x8e
+    unofficial(Sax, Absolute, 4, false, Write),            // 0x8f
+    op(Bcc, Relative, 2, true, Other),                     // 0x90
+    op(Sta, IndirectY, 6, false, Write),                   // 0x91
+    unofficial(Jam, Implied, 2, false, Other),             // 0x92
+    unofficial(Sha, IndirectY, 6, false, Write),           // 0x93
+    op(Sty, ZeroPageX, 4, false, Write),                   // 0x94
+    op(Sta, ZeroPageX, 4, false, Write),                   // 0x95
+    op(Stx, ZeroPageY, 4, false, Write),                   // 0x96
+    unofficial(Sax, ZeroPageY, 4, false, Write),           // 0x97
+    op(Tya, Implied, 2, false, Other),                     // 0x98
+    op(Sta, AbsoluteY, 5, false, Write),                   // 0x99
+    op(Txs, Implied, 2, false, Other),                     // 0x9a
+    unofficial(Tas, AbsoluteY, 5, false, Write),           // 0x9b
+    unofficial(Shy, AbsoluteX, 5, false, Write),           // 0x9c
+    op(Sta, AbsoluteX, 5, false, Write),                   // 0x9d
+    unofficial(Shx, AbsoluteY, 5, false, Write),           // 0x9e
+    unofficial(Sha, AbsoluteY, 5, false, Write),           // 0x9f
+    op(Ldy, Immediate, 2, false, Read),                    // 0xa0
+    op(Lda, IndirectX, 6, false, Read),                    // 0xa1
+    op(Ldx, Immediate, 2, false, Read),                    // 0xa2
+    unofficial(Lax, IndirectX, 6, false, Read),            // 0xa3
+    op(Ldy, ZeroPage, 3, false, Read),                     // 0xa4
+    op(Lda, ZeroPage, 3, false, Read),                     // 0xa5
+    op(Ldx, ZeroPage, 3, false, Read),                     // 0xa6
+    unofficial(Lax, ZeroPage, 3, false, Read),             // 0xa7
+    op(Tay, Implied, 2, false, Other),                     // 0xa8
+    op(Lda, Immediate, 2, false, Read),                    // 0xa9
+    op(Tax, Implied, 2, false, Other),                     // 0xaa
+    unofficial(Lxa, Immediate, 2, false, Read),            // 0xab
+    op(Ldy, Absolute, 4, false, Read),                     // 0xac
+    op(Lda, Absolute, 4, false, Read),                     // 0xad
+    op(Ldx, Absolute, 4, false, Read),                     // 0xae
+    unofficial(Lax, Absolute, 4, false, Read),             // 0xaf
+    op(Bcs, Relative, 2, true, Other),                     // 0xb0
+    op(Lda, IndirectY, 5, true, Read),                     // 0xb1
+    unofficial(Jam, Implied, 2, false, Other),             // 0xb2
+    unofficial(Lax, IndirectY, 5, true, Read),             // 0xb3
+    op(Ldy, ZeroPageX, 4, false, Read),                    // 0xb4
+    op(Lda, ZeroPageX, 4, false, Read),                    // 0xb5
+    op(Ldx, ZeroPageY, 4, false, Read),                    // 0xb6
+    unofficial(Lax, ZeroPageY, 4, false, Read),            // 0xb7
+    op(Clv, Implied, 2, false, Other),                     // 0xb8
+    op(Lda, AbsoluteY, 4, true, Read),                     // 0xb9
+    op(Tsx, Implied, 2, false, Other),                     // 0xba
+    unofficial(Las, AbsoluteY, 4, true, Read),             // 0xbb
+    op(Ldy, AbsoluteX, 4, true, Read),                     // 0xbc
+    op(Lda, AbsoluteX, 4, true, Read),                     // 0xbd
+    op(Ldx, AbsoluteY, 4, true, Read),                     // 0xbe
+    unofficial(Lax, AbsoluteY, 4, true, Read),             // 0xbf
+    op(Cpy, Immediate, 2, false, Read),                    // 0xc0
+    op(Cmp, IndirectX, 6, false, Read),                    // 0xc1
+    unofficial(Nop, Immediate, 2, false, Read),            // 0xc2
+    unofficial(Dcp, IndirectX, 8, false, ReadModifyWrite), // 0xc3
+    op(Cpy, ZeroPage, 3, false, Read),                     // 0xc4
+    op(Cmp, ZeroPage, 3, false, Read),                     // 0xc5
+    op(Dec, ZeroPage, 5, false, ReadModifyWrite),          // 0xc6
+    unofficial(Dcp, ZeroPage, 5, false, ReadModifyWrite),  // 0xc7
+    op(Iny, Implied, 2, false, Other),                     // 0xc8
+    op(Cmp, Immediate, 2, false, Read),                    // 0xc9
+    op(Dex, Implied, 2, false, Other),                     // 0xca
+    unofficial(Axs, Immediate, 2, false, Read),            // 0xcb
+    op(Cpy, Absolute, 4, false, Read),                     // 0xcc
+    op(Cmp, Absolute, 4, false, Read),                     // 0xcd
+    op(Dec, Absolute, 6, false, ReadModifyWrite),          // 0xce
+    unofficial(Dcp, Absolute, 6, false, ReadModifyWrite),  // 0xcf
+    op(Bne, Relative, 2, true, Other),                     // 0xd0
+    op(Cmp, IndirectY, 5, true, Read),                     // 0xd1
+    unofficial(Jam, Implied, 2, false, Other),             // 0xd2
+    unofficial(Dcp, IndirectY, 8, false, ReadModifyWrite), // 0xd3
+    unofficial(Nop, ZeroPageX, 4, false, Read),            // 0xd4
+    op(Cmp, ZeroPageX, 4, false, Read),                    // 0xd5
+    op(Dec, ZeroPageX, 6, false, ReadModifyWrite),         // 0xd6
+    unofficial(Dcp, ZeroPageX, 6, false, ReadModifyWrite), // 0xd7
+    op(Cld, Implied, 2, false, Other),                     // 0xd8
+    op(Cmp, AbsoluteY, 4, true, Read),                     // 0xd9
+    unofficial(Nop, Implied, 2, false, Other),             // 0xda
+    unofficial(Dcp, AbsoluteY, 7, false, ReadModifyWrite), // 0xdb
+    unofficial(Nop, AbsoluteX, 4, true, Read),             // 0xdc
+    op(Cmp, AbsoluteX, 4, true, Read),                     // 0xdd
+    op(Dec, AbsoluteX, 7, false, ReadModifyWrite),         // 0xde
+    unofficial(Dcp, AbsoluteX, 7, false, ReadModifyWrite), // 0xdf
+    op(Cpx, Immediate, 2, false, Read),                    // 0xe0
+    op(Sbc, IndirectX, 6, false, Read),                    // 0xe1
+    unofficial(Nop, Immediate, 2, false, Read),            // 0xe2
+    unofficial(Isc, IndirectX, 8, false, ReadModifyWrite), // 0xe3
+    op(Cpx, ZeroPage, 3, false, Read),                     // 0xe4
+    op(Sbc, ZeroPage, 3, false, Read),                     // 0xe5
+    op(Inc, ZeroPage, 5, false, ReadModifyWrite),          // 0xe6
+    unofficial(Isc, ZeroPage, 5, false, ReadModifyWrite),  // 0xe7
+    op(Inx, Implied, 2, false, Other),                     // 0xe8
+    op(Sbc, Immediate, 2, false, Read),                    // 0xe9
+    op(Nop, Implied, 2, false, Other),                     // 0xea
+    unofficial(Sbc, Immediate, 2, false, Read),            // 0xeb
+    op(Cpx, Absolute, 4, false, Read),                     // 0xec
+    op(Sbc, Absolute, 4, false, Read),                     // 0xed
+    op(Inc, Absolute, 6, false, ReadModifyWrite),          // 0xee
+    unofficial(Isc, Absolute, 6, false, ReadModifyWrite),  // 0xef
+    op(Beq, Relative, 2, true, Other),                     // 0xf0
+    op(Sbc, IndirectY, 5, true, Read),                     // 0xf1
+    unofficial(Jam, Implied, 2, false, Other),             // 0xf2
+    unofficial(Isc, IndirectY, 8, false, ReadModifyWrite), // 0xf3
+    unofficial(Nop, ZeroPageX, 4, false, Read),            // 0xf4
+    op(Sbc, ZeroPageX, 4, false, Read),                    // 0xf5
+    op(Inc, ZeroPageX, 6, false, ReadModifyWrite),         // 0xf6
+    unofficial(Isc, ZeroPageX, 6, false, ReadModifyWrite), // 0xf7
+    op(Sed, Implied, 2, false, Other),                     // 0xf8
+    op(Sbc, AbsoluteY, 4, true, Read),                     // 0xf9
+    unofficial(Nop, Implied, 2, false, Other),             // 0xfa
+    unofficial(Isc, AbsoluteY, 7, false, ReadModifyWrite), // 0xfb
+    unofficial(Nop, AbsoluteX, 4, true, Read),             // 0xfc
+    op(Sbc, AbsoluteX, 4, true, Read),                     // 0xfd
+    op(Inc, AbsoluteX, 7, false, ReadModifyWrite),         // 0xfe
+    unofficial(Isc, AbsoluteX, 7, false, ReadModifyWrite), // 0xff
+];
+
+// Disassembles the instruction at `pc`, given the bytes starting there, e.g. "LDA $0200,X"
+pub fn disassemble(pc: u16, bytes: [u8; 3]) -> String {
+    let Opcode { mnemonic, mode, .. } = OPCODES[bytes[0] as usize];
+    let imm8: u8 = bytes[1];
+    let imm16: u16 = ((bytes[2] as u16) << 8) | (bytes[1] as u16);
+    let operand: String = match mode {
+        Implied => String::new(),
+        Accumulator => String::from("A"),
+        Immediate => format!("#${:02X}", imm8),
+        ZeroPage => format!("${:02X}", imm8),
+        ZeroPageX => format!("${:02X},X", imm8),
+        ZeroPageY => format!("${:02X},Y", imm8),
+        Absolute => format!("${:04X}", imm16),
+        AbsoluteX => format!("${:04X},X", imm16),
+        AbsoluteY => format!("${:04X},Y", imm16),
+        Indirect => format!("(${:04X})", imm16),
+        IndirectX => format!("(${:02X},X)", imm8),
+        IndirectY => format!("(${:02X}),Y", imm8),
+        Relative => format!("${:04X}", pc.wrapping_add(2).wrapping_add(imm8 as i8 as u16)),
+    };
+    if operand.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operand)
+    }
+}
@@ -0,0 +1,704 @@
+/// In pixels
+pub const SCREEN_WIDTH: usize = 256;
+/// In pixels
+pub const SCREEN_HEIGHT: usize = 240;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+const SCANLINES_PER_FRAME: u16 = 262;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+
+const STATUS_VBLANK: u8 = 0b10000000;
+const STATUS_SPRITE_0_HIT: u8 = 0b01000000;
+const STATUS_SPRITE_OVERFLOW: u8 = 0b00100000;
+
+const SPRITES_PER_SCANLINE: usize = 8;
+
+pub const PPUCTRL: u16 = 0x2000;
+pub const PPUCTRL_I: u16 = PPUCTRL % 8;
+pub const PPUMASK: u16 = 0x2001;
+pub const PPUMASK_I: u16 = PPUMASK % 8;
+pub const OAMADDR: u16 = 0x2003;
+pub const OAMADDR_I: u16 = OAMADDR % 8;
+pub const OAMDATA: u16 = 0x2004;
+pub const OAMDATA_I: u16 = OAMDATA % 8;
+pub const PPUSCROLL: u16 = 0x2005;
+pub const PPUSCROLL_I: u16 = PPUSCROLL % 8;
+pub const PPUADDR: u16 = 0x2006;
+pub const PPUADDR_I: u16 = PPUADDR % 8;
+pub const PPUDATA: u16 = 0x2007;
+pub const PPUDATA_I: u16 = PPUDATA % 8;
+pub const PPUSTATUS: u16 = 0x2002;
+pub const PPUSTATUS_I: u16 = PPUSTATUS % 8;
+
+/// How the four nametables the PPU addresses map onto the two that fit in the console's 2 KiB of VRAM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+    /// The top two nametables share one table, and the bottom two the other
+    Horizontal,
+    /// The left two nametables share one table, and the right two the other
+    Vertical,
+    /// All four nametables are the first table
+    SingleScreenLower,
+    /// All four nametables are the second table
+    SingleScreenUpper,
+    /// The cartridge supplies RAM for the other two tables, so all four are separate
+    FourScreen,
+}
+
+pub struct Ppu {
+    pub regs: [u8; 8],
+
+    pub chr: [u8; 0x2000],
+    pub chr_is_ram: bool,
+    pub mirroring: Mirroring,
+    vram: [u8; 0x800],
+    // Four-screen cartridges supply their own RAM for the third and fourth nametables
+    cartridge_vram: [u8; 0x800],
+    pub ppu_ram: [u8; 0x20],
+    pub oam: [u8; 0x100],
+    ppudata: u8,
+    oamdata_is_ff: bool,
+
+    // The loopy scroll registers: the current VRAM address, the temporary VRAM address, the fine
+    // x scroll and the shared write toggle for PPUSCROLL/PPUADDR. v and t are laid out as
+    // 0yyy NNYY YYYX XXXX (fine y, nametable select, coarse y, coarse x).
+    pub v: u16,
+    t: u16,
+    fine_x: u8,
+    w: bool,
+
+    next_tile_id: u8,
+    next_tile_attribute: u8,
+    next_tile_low: u8,
+    next_tile_high: u8,
+    bg_pattern_low: u16,
+    bg_pattern_high: u16,
+    bg_attribute_low: u16,
+    bg_attribute_high: u16,
+
+    secondary_oam: [u8; 4 * SPRITES_PER_SCANLINE],
+    line_sprites: [LineSprite; 64],
+    line_sprite_count: usize,
+    // Lets every sprite on a scanline be drawn, rather than only the first 8, to get rid of flicker
+    pub disable_sprite_limit: bool,
+
+    pub scanline: u16,
+    pub dot: u16,
+    pub frame_complete: bool,
+    odd_frame: bool,
+    pub nmi_pending: bool,
+
+    // Each entry is an index into the system palette in the low 6 bits, with the red, green and blue
+    // emphasis bits above it. It's on the heap so that the console, which is moved around by value while it's built, stays
+    // small enough for a thread's stack in debug builds.
+    pub framebuffer: Box<[u16]>,
+}
+
+struct Sprite {
+    c: u8,
+    r: u8,
+    pattern_table_index: u8,
+    palette_index: u8,
+    priority: bool,
+    h_flip: bool,
+    v_flip: bool,
+}
+
+// A sprite selected by sprite evaluation, with its row of pattern data for the scanline already fetched
+#[derive(Clone, Copy, Default)]
+struct LineSprite {
+    c: u8,
+    palette_index: u8,
+    // Set when the sprite is drawn behind the background
+    priority: bool,
+    pattern_low: u8,
+    pattern_high: u8,
+    is_sprite_0: bool,
+}
+
+fn parse_sprite(data: [u8; 4]) -> Sprite {
+    Sprite {
+        c: data[3],
+        r: data[0],
+        pattern_table_index: data[1],
+        palette_index: data[2] & 0b11,
+        priority: (data[2] & 0b100000) != 0,
+        h_flip: (data[2] & 0b1000000) != 0,
+        v_flip: (data[2] & 0b10000000) != 0,
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Ppu {
+        Ppu {
+            regs: [0; 8],
+            chr: [0; 0x2000],
+            chr_is_ram: false,
+            mirroring: Mirroring::Horizontal,
+            vram: [0; 0x800],
+            cartridge_vram: [0; 0x800],
+            ppu_ram: [0; 0x20],
+            oam: [0; 0x100],
+            ppudata: 0,
+            oamdata_is_ff: false,
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            next_tile_id: 0,
+            next_tile_attribute: 0,
+            next_tile_low: 0,
+            next_tile_high: 0,
+            bg_pattern_low: 0,
+            bg_pattern_high: 0,
+            bg_attribute_low: 0,
+            bg_attribute_high: 0,
+            secondary_oam: [0xff; 4 * SPRITES_PER_SCANLINE],
+            line_sprites: [Default::default(); 64],
+            line_sprite_count: 0,
+            disable_sprite_limit: false,
+            scanline: 0,
+            dot: 0,
+            frame_complete: false,
+            odd_frame: false,
+            nmi_pending: false,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT].into_boxed_slice(),
+        }
+    }
+}
+
+impl Ppu {
+    fn get_bg_pattern_table_base(&self) -> u16 {
+        (((self.regs[PPUCTRL_I as usize] >> 4) & 1) as u16) * 0x1000
+    }
+
+    fn get_sprite_pattern_table_base(&self) -> u16 {
+        (((self.regs[PPUCTRL_I as usize] >> 3) & 1) as u16) * 0x1000
+    }
+
+    fn get_vram_increment(&self) -> u16 {
+        if (self.regs[PPUCTRL_I as usize] & 0b100) == 0 {
+            1
+        } else {
+            32
+        }
+    }
+
+    fn nmi_is_enabled(&self) -> bool {
+        (self.regs[PPUCTRL_I as usize] & 0b10000000) != 0
+    }
+
+    fn is_in_8x16_mode(&self) -> bool {
+        (self.regs[PPUCTRL_I as usize] & 0b00100000) != 0
+    }
+
+    fn sprite_is_enabled(&self) -> bool {
+        (self.regs[PPUMASK_I as usize] & 0b00010000) != 0
+    }
+
+    fn background_is_enabled(&self) -> bool {
+        (self.regs[PPUMASK_I as usize] & 0b00001000) != 0
+    }
+
+    fn shows_left_sprites(&self) -> bool {
+        (self.regs[PPUMASK_I as usize] & 0b00000100) != 0
+    }
+
+    fn shows_left_background(&self) -> bool {
+        (self.regs[PPUMASK_I as usize] & 0b00000010) != 0
+    }
+
+    fn is_greyscale(&self) -> bool {
+        (self.regs[PPUMASK_I as usize] & 0b00000001) != 0
+    }
+
+    // The red, green and blue emphasis bits, in that order from least significant
+    fn get_emphasis(&self) -> u8 {
+        self.regs[PPUMASK_I as usize] >> 5
+    }
+
+    fn rendering_is_enabled(&self) -> bool {
+        self.background_is_enabled() || self.sprite_is_enabled()
+    }
+
+    fn is_rendering(&self) -> bool {
+        self.rendering_is_enabled() && (self.scanline < SCREEN_HEIGHT as u16 || self.scanline == PRE_RENDER_SCANLINE)
+    }
+
+    // Maps a nametable address onto one of the four physical 1 KiB nametables, and the offset within it
+    fn nametable_location(&self, addr: u16) -> (u16, usize) {
+        let logical_table: u16 = (addr >> 10) & 0b11;
+        let physical_table: u16 = match self.mirroring {
+            Mirroring::Horizontal => logical_table >> 1,
+            Mirroring::Vertical => logical_table & 1,
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => logical_table,
+        };
+        (physical_table, (addr & 0x3ff) as usize)
+    }
+
+    fn nametable_read(&self, addr: u16) -> u8 {
+        match self.nametable_location(addr) {
+            (0, offset) => self.vram[offset],
+            (1, offset) => self.vram[0x400 + offset],
+            (2, offset) => self.cartridge_vram[offset],
+            (_, offset) => self.cartridge_vram[0x400 + offset],
+        }
+    }
+
+    fn nametable_write(&mut self, addr: u16, val: u8) {
+        match self.nametable_location(addr) {
+            (0, offset) => self.vram[offset] = val,
+            (1, offset) => self.vram[0x400 + offset] = val,
+            (2, offset) => self.cartridge_vram[offset] = val,
+            (_, offset) => self.cartridge_vram[0x400 + offset] = val,
+        }
+    }
+
+    pub fn ppu_read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..0x2000 => self.chr[addr as usize],
+            0x2000..0x3f00 => self.nametable_read(addr),
+            0x3f00..0x4000 => self.ppu_ram[palette_index(addr)],
+            0x4000..=0xffff => self.ppu_read(addr % 0x4000),
+        }
+    }
+
+    pub fn ppu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..0x2000 => {
+                if self.chr_is_ram {
+                    self.chr[addr as usize] = val
+                }
+            }
+            0x2000..0x3f00 => self.nametable_write(addr, val),
+            0x3f00..0x4000 => self.ppu_ram[palette_index(addr)] = val,
+            0x4000..=0xffff => self.ppu_write(addr % 0x4000, val),
+        }
+    }
+
+    pub fn read_register(&mut self, i: u16) -> u8 {
+        match i {
+            PPUSTATUS_I => {
+                let result: u8 = self.regs[i as usize];
+                self.regs[i as usize] &= !STATUS_VBLANK;
+                self.w = false;
+                result
+            }
+            PPUDATA_I => {
+                let addr: u16 = self.v & 0x3fff;
+                let result: u8 = if addr >= 0x3f00 {
+                    // Palette reads aren't delayed, but the buffer still gets filled, from the nametable "underneath" the palette
+                    self.ppudata = self.ppu_read(addr - 0x1000);
+                    self.ppu_read(addr)
+                } else {
+                    let result: u8 = self.ppudata;
+                    self.ppudata = self.ppu_read(addr);
+                    result
+                };
+                self.increment_vram_addr();
+                result
+            }
+            OAMDATA_I => {
+                if self.oamdata_is_ff {
+                    0xff
+                } else {
+                    self.oam[self.regs[OAMADDR_I as usize] as usize]
+                }
+            }
+            _ => self.regs[i as usize],
+        }
+    }
+
+    // The reset line clears the control and mask registers, the scroll and the write toggle. Memory is left alone.
+    pub fn reset(&mut self) {
+        self.regs[PPUCTRL_I as usize] = 0;
+        self.regs[PPUMASK_I as usize] = 0;
+        self.t = 0;
+        self.fine_x = 0;
+        self.w = false;
+        self.ppudata = 0;
+        self.odd_frame = false;
+    }
+
+    pub fn write_register(&mut self, i: u16, val: u8) {
+        match i {
+            PPUCTRL_I => {
+                let nmi_was_enabled: bool = self.nmi_is_enabled();
+                self.regs[i as usize] = val;
+                self.t = (self.t & !0x0c00) | (((val & 0b11) as u16) << 10);
+                // Enabling NMI while the vblank flag is still set raises an NMI immediately
+                if !nmi_was_enabled && self.nmi_is_enabled() && (self.regs[PPUSTATUS_I as usize] & STATUS_VBLANK) != 0 {
+                    self.nmi_pending = true;
+                }
+            }
+            OAMDATA_I => {
+                let oam_addr: u8 = self.regs[OAMADDR_I as usize];
+                self.oam[oam_addr as usize] = val;
+                self.regs[OAMADDR_I as usize] = oam_addr.wrapping_add(1);
+            }
+            PPUADDR_I => {
+                if self.w {
+                    self.t = (self.t & 0xff00) | val as u16;
+                    self.v = self.t;
+                } else {
+                    // The first write also clears bit 14 of t
+                    self.t = (self.t & 0x00ff) | (((val & 0x3f) as u16) << 8);
+                }
+                self.w = !self.w;
+            }
+            PPUSCROLL_I => {
+                if self.w {
+                    self.t = (self.t & !0x73e0) | (((val & 0b111) as u16) << 12) | (((val >> 3) as u16) << 5);
+                } else {
+                    self.t = (self.t & !0x001f) | (val >> 3) as u16;
+                    self.fine_x = val & 0b111;
+                }
+                self.w = !self.w;
+            }
+            PPUDATA_I => {
+                self.ppu_write(self.v, val);
+                self.increment_vram_addr();
+            }
+            // Read-only, and its flags are only ever set and cleared by the PPU itself
+            PPUSTATUS_I => {}
+            _ => self.regs[i as usize] = val,
+        }
+    }
+
+    fn increment_vram_addr(&mut self) {
+        if self.is_rendering() {
+            // Accessing PPUDATA mid-render bumps both coarse x and y instead of adding the increment
+            self.increment_coarse_x();
+            self.increment_y();
+        } else {
+            self.v = self.v.wrapping_add(self.get_vram_increment()) & 0x7fff;
+        }
+    }
+
+    fn increment_coarse_x(&mut self) {
+        if (self.v & 0x001f) == 31 {
+            // Wrap into the horizontally adjacent nametable
+            self.v &= !0x001f;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if (self.v & 0x7000) != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y: u16 = (self.v & 0x03e0) >> 5;
+        if coarse_y == 29 {
+            // Row 29 is the last row of tiles, so wrap into the vertically adjacent nametable
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            // Rows 30 and 31 are the attribute table; wrapping from there stays in the same nametable
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03e0) | (coarse_y << 5);
+    }
+
+    fn copy_horizontal_bits(&mut self) {
+        self.v = (self.v & !0x041f) | (self.t & 0x041f);
+    }
+
+    fn copy_vertical_bits(&mut self) {
+        self.v = (self.v & !0x7be0) | (self.t & 0x7be0);
+    }
+
+    fn load_background_shifters(&mut self) {
+        self.bg_pattern_low = (self.bg_pattern_low & 0xff00) | self.next_tile_low as u16;
+        self.bg_pattern_high = (self.bg_pattern_high & 0xff00) | self.next_tile_high as u16;
+        self.bg_attribute_low = (self.bg_attribute_low & 0xff00) | if (self.next_tile_attribute & 0b01) != 0 { 0xff } else { 0x00 };
+        self.bg_attribute_high = (self.bg_attribute_high & 0xff00) | if (self.next_tile_attribute & 0b10) != 0 { 0xff } else { 0x00 };
+    }
+
+    fn shift_background_shifters(&mut self) {
+        self.bg_pattern_low <<= 1;
+        self.bg_pattern_high <<= 1;
+        self.bg_attribute_low <<= 1;
+        self.bg_attribute_high <<= 1;
+    }
+
+    // Performs the background fetches for the current dot of a rendering scanline
+    fn fetch_background(&mut self) {
+        if (2..=257).contains(&self.dot) || (321..=337).contains(&self.dot) {
+            self.shift_background_shifters();
+            match (self.dot - 1) % 8 {
+                0 => {
+                    self.load_background_shifters();
+                    self.next_tile_id = self.ppu_read(0x2000 | (self.v & 0x0fff));
+                }
+                2 => {
+                    let attribute_addr: u16 = 0x23c0 | (self.v & 0x0c00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+                    // Each attribute byte covers 4x4 tiles; pick the 2x2 quadrant that v is in
+                    let shift: u16 = ((self.v >> 4) & 0b100) | (self.v & 0b10);
+                    self.next_tile_attribute = (self.ppu_read(attribute_addr) >> shift) & 0b11;
+                }
+                4 => {
+                    let fine_y: u16 = (self.v >> 12) & 0b111;
+                    self.next_tile_low = self.ppu_read(self.get_bg_pattern_table_base() + self.next_tile_id as u16 * 16 + fine_y);
+                }
+                6 => {
+                    let fine_y: u16 = (self.v >> 12) & 0b111;
+                    self.next_tile_high = self.ppu_read(self.get_bg_pattern_table_base() + self.next_tile_id as u16 * 16 + fine_y + 8);
+                }
+                7 => self.increment_coarse_x(),
+                _ => {}
+            }
+        }
+
+        if self.dot == 256 {
+            self.increment_y();
+        } else if self.dot == 257 {
+            self.load_background_shifters();
+            self.copy_horizontal_bits();
+        } else if self.scanline == PRE_RENDER_SCANLINE && (280..=304).contains(&self.dot) {
+            self.copy_vertical_bits();
+        }
+    }
+
+    // Selects the sprites for the next scanline and fetches their pattern data
+    fn fetch_sprites(&mut self) {
+        if self.dot == 257 {
+            if self.scanline == PRE_RENDER_SCANLINE {
+                // Nothing is evaluated on the pre-render scanline, so no sprites appear on scanline 0
+                self.line_sprite_count = 0;
+            } else {
+                self.evaluate_sprites();
+            }
+        }
+        if (257..=320).contains(&self.dot) {
+            self.regs[OAMADDR_I as usize] = 0;
+        }
+    }
+
+    fn sprite_is_on_scanline(&self, sprite_y: u8) -> bool {
+        let y: usize = self.scanline as usize;
+        y >= sprite_y as usize && y < sprite_y as usize + self.get_sprite_height()
+    }
+
+    fn evaluate_sprites(&mut self) {
+        self.secondary_oam = [0xff; 4 * SPRITES_PER_SCANLINE];
+        self.line_sprite_count = 0;
+
+        let mut n: usize = 0;
+        let mut found: usize = 0;
+        while n < 64 && found < SPRITES_PER_SCANLINE {
+            if self.sprite_is_on_scanline(self.oam[n * 4]) {
+                self.secondary_oam[found * 4..(found + 1) * 4].copy_from_slice(&self.oam[n * 4..(n + 1) * 4]);
+                found += 1;
+            }
+            n += 1;
+        }
+        let first_unevaluated: usize = n;
+
+        // Once secondary OAM is full, the PPU keeps looking for a ninth sprite to set the overflow flag. Because of
+        // a hardware bug it increments the byte offset within each entry along with the entry, so it compares
+        // tile indices, attributes and x positions against the scanline as if they were y positions.
+        let mut m: usize = 0;
+        while n < 64 {
+            if self.sprite_is_on_scanline(self.oam[n * 4 + m]) {
+                self.regs[PPUSTATUS_I as usize] |= STATUS_SPRITE_OVERFLOW;
+                break;
+            }
+            n += 1;
+            m = (m + 1) % 4;
+        }
+
+        // Sprite 0 can only have been copied into the first slot
+        let sprite_0_found: bool = self.sprite_is_on_scanline(self.oam[0]);
+        for i in 0..found {
+            let mut raw_sprite_data = [0; 4];
+            raw_sprite_data.copy_from_slice(&self.secondary_oam[i * 4..(i + 1) * 4]);
+            self.load_line_sprite(raw_sprite_data, i == 0 && sprite_0_found);
+        }
+
+        if self.disable_sprite_limit && found == SPRITES_PER_SCANLINE {
+            for n in first_unevaluated..64 {
+                if self.sprite_is_on_scanline(self.oam[n * 4]) {
+                    let mut raw_sprite_data = [0; 4];
+                    raw_sprite_data.copy_from_slice(&self.oam[n * 4..(n + 1) * 4]);
+                    self.load_line_sprite(raw_sprite_data, false);
+                }
+            }
+        }
+    }
+
+    fn load_line_sprite(&mut self, raw_sprite_data: [u8; 4], is_sprite_0: bool) {
+        let sprite: Sprite = parse_sprite(raw_sprite_data);
+        let pattern_addr: u16 = self.get_sprite_pattern_addr(&sprite, self.scanline as usize - sprite.r as usize);
+        let mut pattern_low: u8 = self.ppu_read(pattern_addr);
+        let mut pattern_high: u8 = self.ppu_read(pattern_addr + 8);
+        if sprite.h_flip {
+            pattern_low = pattern_low.reverse_bits();
+            pattern_high = pattern_high.reverse_bits();
+        }
+
+        self.line_sprites[self.line_sprite_count] = LineSprite {
+            c: sprite.c,
+            palette_index: sprite.palette_index,
+            priority: sprite.priority,
+            pattern_low,
+            pattern_high,
+            is_sprite_0,
+        };
+        self.line_sprite_count += 1;
+    }
+
+    // Advances the PPU by one dot. The PPU runs at 3 dots per CPU cycle.
+    pub fn tick(&mut self) {
+        if self.is_rendering() {
+            self.fetch_background();
+            self.fetch_sprites();
+        }
+
+        if self.scanline < SCREEN_HEIGHT as u16 && (1..=SCREEN_WIDTH as u16).contains(&self.dot) {
+            self.render_pixel((self.dot - 1) as usize, self.scanline as usize);
+        }
+
+        if self.dot == 1 {
+            if self.scanline == VBLANK_SCANLINE {
+                self.regs[PPUSTATUS_I as usize] |= STATUS_VBLANK;
+                if self.nmi_is_enabled() {
+                    self.nmi_pending = true;
+                }
+            } else if self.scanline == PRE_RENDER_SCANLINE {
+                self.regs[PPUSTATUS_I as usize] &= !(STATUS_VBLANK | STATUS_SPRITE_0_HIT | STATUS_SPRITE_OVERFLOW);
+            }
+        }
+
+        self.dot += 1;
+        if self.scanline == PRE_RENDER_SCANLINE && self.dot == DOTS_PER_SCANLINE - 1 && self.odd_frame && self.rendering_is_enabled() {
+            // The last dot of the pre-render scanline is skipped on odd frames while rendering, making the average
+            // frame 29780.5 CPU cycles long
+            self.dot += 1;
+        }
+        if self.dot == DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == SCREEN_HEIGHT as u16 {
+                // The post-render scanline; the visible part of the frame is done
+                self.frame_complete = true;
+            } else if self.scanline == SCANLINES_PER_FRAME {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+    }
+
+    fn render_pixel(&mut self, x: usize, y: usize) {
+        if !self.rendering_is_enabled() {
+            // With rendering off, the PPU outputs the backdrop color, unless v points into palette RAM
+            let addr: u16 = if (self.v & 0x3f00) == 0x3f00 { self.v & 0x3fff } else { 0x3f00 };
+            self.framebuffer[y * SCREEN_WIDTH + x] = self.output_color(self.ppu_read(addr));
+            return;
+        }
+
+        let (bg_palette_index, bg_px) = if self.background_is_enabled() && (x >= 8 || self.shows_left_background()) { self.background_pixel() } else { (0, 0) };
+
+        if bg_px != 0 && self.sprite_0_hit_possible(x) && self.line_sprites[..self.line_sprite_count].iter().any(|sprite| sprite.is_sprite_0 && line_sprite_px(sprite, x) != 0) {
+            self.regs[PPUSTATUS_I as usize] |= STATUS_SPRITE_0_HIT;
+        }
+
+        // The first opaque sprite in OAM order wins, even if it ends up hidden behind the background
+        let front_sprite: Option<(&LineSprite, u8)> = if self.sprite_is_enabled() && (x >= 8 || self.shows_left_sprites()) {
+            self.line_sprites[..self.line_sprite_count].iter().map(|sprite| (sprite, line_sprite_px(sprite, x))).find(|&(_, sprite_px)| sprite_px != 0)
+        } else {
+            None
+        };
+
+        let palette_addr: u16 = match front_sprite {
+            Some((sprite, sprite_px)) if bg_px == 0 || !sprite.priority => 0x3f10 + 4 * sprite.palette_index as u16 + sprite_px as u16,
+            _ if bg_px != 0 => 0x3f00 + 4 * bg_palette_index as u16 + bg_px as u16,
+            // Transparent everywhere, so the universal background color shows through
+            _ => 0x3f00,
+        };
+        let color: u8 = self.ppu_read(palette_addr);
+
+        self.framebuffer[y * SCREEN_WIDTH + x] = self.output_color(color);
+    }
+
+    // Applies greyscale and attaches the emphasis bits to a palette RAM entry
+    fn output_color(&self, color: u8) -> u16 {
+        let index: u8 = if self.is_greyscale() { color & 0x30 } else { color & 0x3f };
+        ((self.get_emphasis() as u16) << 6) | index as u16
+    }
+
+    // Whether an opaque sprite 0 pixel over an opaque background pixel in column x can set the hit flag
+    fn sprite_0_hit_possible(&self, x: usize) -> bool {
+        if !self.background_is_enabled() || !self.sprite_is_enabled() {
+            return false;
+        }
+        // The hit never happens at x=255, nor in the leftmost 8 pixels if either layer is clipped there
+        if x == 255 || (x < 8 && (!self.shows_left_background() || !self.shows_left_sprites())) {
+            return false;
+        }
+        (self.regs[PPUSTATUS_I as usize] & STATUS_SPRITE_0_HIT) == 0
+    }
+
+    // Returns the palette index and the pixel value of the background pixel at the front of the shifters
+    fn background_pixel(&self) -> (u8, u8) {
+        let mux: u16 = 0x8000 >> self.fine_x;
+        let px: u8 = (((self.bg_pattern_high & mux) != 0) as u8) << 1 | ((self.bg_pattern_low & mux) != 0) as u8;
+        let palette_index: u8 = (((self.bg_attribute_high & mux) != 0) as u8) << 1 | ((self.bg_attribute_low & mux) != 0) as u8;
+        (palette_index, px)
+    }
+
+    fn get_sprite_height(&self) -> usize {
+        if self.is_in_8x16_mode() {
+            16
+        } else {
+            8
+        }
+    }
+
+    // Returns the address of the low bitplane of the given row of a sprite, counting from its top
+    fn get_sprite_pattern_addr(&self, sprite: &Sprite, row: usize) -> u16 {
+        // Vertical flipping flips the whole sprite, so in 8x16 mode it swaps the two tiles too
+        let row: u16 = (if sprite.v_flip { self.get_sprite_height() - 1 - row } else { row }) as u16;
+        if self.is_in_8x16_mode() {
+            // Bit 0 of the tile index selects the pattern table, and the top tile is always the even one
+            let pattern_table_base: u16 = (sprite.pattern_table_index & 1) as u16 * 0x1000;
+            let tile_index: u16 = (sprite.pattern_table_index & 0xfe) as u16 + row / 8;
+            pattern_table_base + tile_index * 16 + row % 8
+        } else {
+            self.get_sprite_pattern_table_base() + sprite.pattern_table_index as u16 * 16 + row
+        }
+    }
+}
+
+// Maps a palette RAM address to an index into palette RAM. The first entry of each sprite palette is shared with the
+// corresponding background palette, so $3F10/$3F14/$3F18/$3F1C mirror $3F00/$3F04/$3F08/$3F0C.
+fn palette_index(addr: u16) -> usize {
+    let index: usize = (addr % 0x20) as usize;
+    if index & 0x13 == 0x10 {
+        index & 0x0f
+    } else {
+        index
+    }
+}
+
+// Returns the pixel value of a sprite at column x of its scanline, or 0 if it doesn't cover x
+fn line_sprite_px(sprite: &LineSprite, x: usize) -> u8 {
+    if x < sprite.c as usize || x >= sprite.c as usize + 8 {
+        0
+    } else {
+        pattern_px(sprite.pattern_low, sprite.pattern_high, x - sprite.c as usize)
+    }
+}
+
+// Combines the low and high bitplanes of a pattern table row into the pixel value at column `col`
+fn pattern_px(low: u8, high: u8, col: usize) -> u8 {
+    ((low >> (7 - col)) & 1) | (((high >> (7 - col)) & 1) << 1)
+}
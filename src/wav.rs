@@ -0,0 +1,54 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+const BYTES_PER_SAMPLE: u32 = BITS_PER_SAMPLE as u32 / 8;
+
+// Writes mono 16-bit PCM WAV files. The header's sizes are only filled in by finish.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    samples_written: u64,
+}
+
+impl WavWriter {
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<WavWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?; // RIFF chunk size, filled in by finish
+        file.write_all(b"WAVE")?;
+
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?; // PCM
+        file.write_all(&1u16.to_le_bytes())?; // Mono
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * BYTES_PER_SAMPLE).to_le_bytes())?; // Byte rate
+        file.write_all(&(BYTES_PER_SAMPLE as u16).to_le_bytes())?; // Block align
+        file.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?; // Data chunk size, filled in by finish
+        Ok(WavWriter { file, samples_written: 0 })
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            let sample: i16 = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples_written += samples.len() as u64;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        // RIFF sizes are 32-bit, so a recording longer than about 12 hours at 48 kHz has its sizes clamped to the largest they can hold
+        let data_size: u32 = self.samples_written.checked_mul(BYTES_PER_SAMPLE as u64).and_then(|size: u64| u32::try_from(size).ok()).unwrap_or(u32::MAX).min(u32::MAX - (HEADER_SIZE - 8));
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.file.write_all(&data_size.to_le_bytes())?;
+        self.file.flush()
+    }
+}